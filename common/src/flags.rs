@@ -0,0 +1,111 @@
+#[allow(unused_imports)]
+use crate::prelude::*;
+
+/// Typed view over the bits stored in `Register::Flags`
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Flags(u16);
+
+macro_rules! flag {
+    ($get:ident, $set:ident, $FLAG:ident) => {
+        pub fn $get(&self) -> bool {
+            self.contains(Self::$FLAG)
+        }
+
+        pub fn $set(&mut self, value : bool) {
+            self.set(Self::$FLAG, value)
+        }
+    };
+}
+
+impl Flags {
+    pub const ZERO : Self = Self(1 << 0);
+    pub const CARRY : Self = Self(1 << 1);
+    pub const SIGN : Self = Self(1 << 2);
+    pub const OVERFLOW : Self = Self(1 << 3);
+    pub const INTERRUPT : Self = Self(1 << 4);
+
+    pub fn empty() -> Self {
+        Self(0)
+    }
+
+    pub fn bits(&self) -> u16 {
+        self.0
+    }
+
+    pub fn contains(&self, other : Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    pub fn set(&mut self, other : Self, value : bool) {
+        if value {
+            self.0 |= other.0;
+        } else {
+            self.0 &= !other.0;
+        }
+    }
+
+    flag!(zero, set_zero, ZERO);
+    flag!(carry, set_carry, CARRY);
+    flag!(sign, set_sign, SIGN);
+    flag!(overflow, set_overflow, OVERFLOW);
+    flag!(interrupt_enable, set_interrupt_enable, INTERRUPT);
+}
+
+impl core::ops::BitOr for Flags {
+    type Output = Self;
+
+    fn bitor(self, rhs : Self) -> Self {
+        Self(self.0 | rhs.0)
+    }
+}
+
+impl From<u16> for Flags {
+    fn from(value : u16) -> Self {
+        Self(value)
+    }
+}
+
+impl From<Flags> for u16 {
+    fn from(value : Flags) -> Self {
+        value.0
+    }
+}
+
+impl core::fmt::Display for Flags {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        let names = [
+            (Self::ZERO, 'Z'),
+            (Self::CARRY, 'C'),
+            (Self::SIGN, 'S'),
+            (Self::OVERFLOW, 'O'),
+            (Self::INTERRUPT, 'I'),
+        ];
+        for (flag, name) in names {
+            write!(f, "{}", if self.contains(flag) { name } else { '-' })?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn named() {
+        let mut flags = Flags::from(0x0003);
+        assert!(flags.zero() && flags.carry());
+        assert!(!flags.sign() && !flags.overflow() && !flags.interrupt_enable());
+
+        flags.set_zero(false);
+        flags.set_interrupt_enable(true);
+        assert_eq!(flags, Flags::CARRY | Flags::INTERRUPT);
+        assert_eq!(u16::from(flags), 0x0012);
+    }
+
+    #[test]
+    fn display() {
+        assert_eq!(Flags::empty().to_string(), "-----");
+        assert_eq!((Flags::ZERO | Flags::OVERFLOW).to_string(), "Z--O-");
+    }
+}
@@ -1,53 +1,81 @@
 #[allow(unused_imports)]
 use crate::prelude::*;
+#[allow(unused_imports)]
 use super::*;
+#[cfg(feature = "std")]
+use std::io::Write;
 
-impl Instruction {
-    pub fn compile(&self) -> Vec<u8> {
-        use Instruction::*;
-        match self {
-            Nop => vec![self.opcode(), 0x00],
+/// Lays out the params of an instruction as the table describes them, given how many operation
+/// and extra register bytes it has: opcode, operations, registers, extra registers and immediates.
+pub(super) struct Encoder<'a> {
+    bytes : &'a mut [u8],
+    ops : usize,
+    extras : usize,
+    next_op : usize,
+    next_extra : usize,
+    next_imm : usize,
+}
 
-            MovI2IP(src, dest) | MovIP2IP(src, dest)
-                => vec![self.opcode(), 0x00, src.get_byte(0), src.get_byte(1), dest.get_byte(0), dest.get_byte(1)],
+impl<'a> Encoder<'a> {
+    /// The bytes must be exactly as long as the instruction
+    pub(super) fn new(bytes : &'a mut [u8], opcode : u8, ops : usize, extras : usize) -> Self {
+        bytes.fill(0x00);
+        bytes[0] = opcode;
+        Self { bytes, ops, extras, next_op : 0, next_extra : 0, next_imm : 0 }
+    }
 
-            MovI2R(value, dest) | MovI2RP(value, dest) | MovIP2R(value, dest) | MovIP2RP(value, dest)
-                => vec![self.opcode(), dest.as_dest(), value.get_byte(0), value.get_byte(1)],
+    pub(super) fn op(&mut self, code : u8) {
+        self.bytes[1 + self.next_op] = code;
+        self.next_op += 1;
+    }
 
-            MovR2IP(src, dest) | MovRP2IP(src, dest)
-                => vec![self.opcode(), src.as_src(), dest.get_byte(0), dest.get_byte(1)],
+    pub(super) fn src(&mut self, reg : &Register) {
+        self.bytes[1 + self.ops] |= reg.as_src();
+    }
 
-            MovR2R(src, dest) | MovR2RP(src, dest) | MovRP2R(src, dest) | MovRP2RP(src, dest)
-                => vec![self.opcode(), src.as_src_with(dest)],
-        }
+    pub(super) fn dest(&mut self, reg : &Register) {
+        self.bytes[1 + self.ops] |= reg.as_dest();
     }
 
-    pub fn opcode(&self) -> u8 {
-        use Instruction::*;
-        macro_rules! case {
-            ($src:ident, $start:literal) => {
-                match $src.width() {
-                    Width::Byte => $start,
-                    Width::Word => $start + 1,
-                }
-            };
-        }
+    fn extra_byte(&mut self, byte : u8) {
+        self.bytes[2 + self.ops + self.next_extra] = byte;
+        self.next_extra += 1;
+    }
 
-        match self {
-            Nop => 0x00,
-            MovI2R(value, _) => case!(value, 0x01),
-            MovI2RP(value, _) => case!(value, 0x03),
-            MovI2IP(value, _) => case!(value, 0x05),
-            MovIP2R(_, dest) => case!(dest, 0x07),
-            MovIP2RP(_, _) => 0x09, // TODO: Width of the data being sent?
-            MovIP2IP(_, _) => 0x0A,
-            MovR2R(src, _) => case!(src, 0x0B),
-            MovR2RP(src, _) => case!(src, 0x0D),
-            MovR2IP(src, _) => case!(src, 0x0F),
-            MovRP2R(_, dest) => case!(dest, 0x11),
-            MovRP2RP(_, _) => 0x13,
-            MovRP2IP(_, _) => 0x14,
-        }
+    pub(super) fn extra(&mut self, reg : &Register) {
+        self.extra_byte(reg.as_src());
+    }
+
+    /// The registers byte and mode of the address take extra bytes, the displacement is an immediate
+    pub(super) fn addr(&mut self, addr : &Address) {
+        let index = addr.index.map_or(0, |(index, _)| index.as_dest());
+        self.extra_byte(addr.base.as_src() | index);
+        self.extra_byte(addr.mode());
+        self.imm(&addr.disp);
+    }
+
+    pub(super) fn imm(&mut self, imm : &Immediate) {
+        let start = 2 + self.ops + self.extras + 2 * self.next_imm;
+        self.bytes[start] = imm.get_byte(0);
+        self.bytes[start + 1] = imm.get_byte(1);
+        self.next_imm += 1;
+    }
+}
+
+impl Instruction {
+    pub fn compile(&self) -> Vec<u8> {
+        let mut bytes = [0; Self::MAX_LEN as usize];
+        let len = self.encode(&mut bytes).expect("every instruction fits MAX_LEN");
+        bytes[..len].to_vec()
+    }
+
+    /// Encodes into `writer` through a buffer on the stack, returns how many bytes it wrote
+    #[cfg(feature = "std")]
+    pub fn encode_to(&self, mut writer : impl Write) -> Result<usize> {
+        let mut bytes = [0; Self::MAX_LEN as usize];
+        let len = self.encode(&mut bytes)?;
+        writer.write_all(&bytes[..len]).map_err(|err| Error::Misc(err.to_string()))?;
+        Ok(len)
     }
 }
 
@@ -91,6 +119,44 @@ mod test {
         };
     }
 
+    macro_rules! op_test_case {
+        ($ident:ident, $($op:expr, $param:expr, $bytes:expr);+) => {
+            #[test]
+            fn $ident() {
+                $(
+                    let instr = Instruction::$ident($op, $param).unwrap();
+                    let bytes = instr.compile();
+                    assert_eq!(bytes, $bytes);
+                    assert_eq!(Instruction::decompile(&bytes), Ok(instr));
+                )+
+            }
+        };
+
+        ($ident:ident, $($op:expr, $left:expr, $right:expr, $bytes:expr);+) => {
+            #[test]
+            fn $ident() {
+                $(
+                    let instr = Instruction::$ident($op, $left, $right).unwrap();
+                    let bytes = instr.compile();
+                    assert_eq!(bytes, $bytes);
+                    assert_eq!(Instruction::decompile(&bytes), Ok(instr));
+                )+
+            }
+        };
+
+        ($ident:ident, $($op:expr, $width:expr, $left:expr, $right:expr, $bytes:expr);+) => {
+            #[test]
+            fn $ident() {
+                $(
+                    let instr = Instruction::$ident($op, $width, $left, $right).unwrap();
+                    let bytes = instr.compile();
+                    assert_eq!(bytes, $bytes);
+                    assert_eq!(Instruction::decompile(&bytes), Ok(instr));
+                )+
+            }
+        };
+    }
+
     test_case!(nop, [0x00, 0x00]);
     test_case!(
         movi2r,
@@ -112,13 +178,15 @@ mod test {
         Immediate::word(0x600D), Register::rb0(), [0x07, 0x00, 0x0D, 0x60];
         Immediate::word(0x600D), Register::r0(), [0x08, 0x00, 0x0D, 0x60]
     );
-    test_case!(
+    op_test_case!(
         movip2rp,
-        Immediate::word(0x600D), Register::r0(), [0x09, 0x00, 0x0D, 0x60]
+        Width::Byte, Immediate::word(0x600D), Register::r0(), [0x18, 0x00, 0x0D, 0x60];
+        Width::Word, Immediate::word(0x600D), Register::r0(), [0x19, 0x00, 0x0D, 0x60]
     );
-    test_case!(
+    op_test_case!(
         movip2ip,
-        Immediate::word(0x600D), Immediate::word(0xF337), [0x0A, 0x00, 0x0D, 0x60, 0x37, 0xF3]
+        Width::Byte, Immediate::word(0x600D), Immediate::word(0xF337), [0x1A, 0x00, 0x0D, 0x60, 0x37, 0xF3];
+        Width::Word, Immediate::word(0x600D), Immediate::word(0xF337), [0x1B, 0x00, 0x0D, 0x60, 0x37, 0xF3]
     );
     test_case!(
         movr2r,
@@ -140,12 +208,318 @@ mod test {
         Register::r0(), Register::rb1(), [0x11, 0x10];
         Register::r0(), Register::r1(), [0x12, 0x10]
     );
-    test_case!(
+    op_test_case!(
         movrp2rp,
-        Register::r0(), Register::r1(), [0x13, 0x10]
+        Width::Byte, Register::r0(), Register::r1(), [0x1C, 0x10];
+        Width::Word, Register::r0(), Register::r1(), [0x1D, 0x10]
     );
-    test_case!(
+    op_test_case!(
         movrp2ip,
-        Register::r0(), Immediate::word(0x600D), [0x14, 0x00, 0x0D, 0x60]
+        Width::Word, Register::r0(), Immediate::word(0x600D), [0x1F, 0x00, 0x0D, 0x60]
+    );
+    test_case!(
+        calli,
+        Immediate::word(0x600D), [0x54, 0x00, 0x0D, 0x60]
+    );
+    test_case!(
+        callip,
+        Immediate::word(0x600D), [0x55, 0x00, 0x0D, 0x60]
+    );
+    test_case!(
+        callr,
+        Register::r5(), [0x56, 0x05]
+    );
+    test_case!(
+        callrp,
+        Register::r5(), [0x57, 0x05]
+    );
+    test_case!(ret, [0x58, 0x00]);
+    test_case!(
+        int,
+        Immediate::byte(0x21), [0x59, 0x00, 0x21, 0x00]
+    );
+    test_case!(iret, [0x5A, 0x00]);
+    test_case!(sti, [0x5B, 0x00]);
+    test_case!(cli, [0x5C, 0x00]);
+    test_case!(hlt, [0x5D, 0x00]);
+    test_case!(wfi, [0x5E, 0x00]);
+    test_case!(
+        pushi,
+        Immediate::byte(0x60), [0x60, 0x00, 0x60, 0x00];
+        Immediate::word(0x600D), [0x61, 0x00, 0x0D, 0x60]
     );
+    test_case!(
+        pushr,
+        Register::rb1(), [0x62, 0x01];
+        Register::r1(), [0x63, 0x01]
+    );
+    test_case!(
+        popr,
+        Register::rb1(), [0x64, 0x10];
+        Register::r1(), [0x65, 0x10]
+    );
+
+    op_test_case!(
+        alui2r,
+        AluOp::Add, Immediate::byte(0x60), Register::rb0(), [0x21, 0x00, 0x00, 0x60, 0x00];
+        AluOp::Add, Immediate::word(0x600D), Register::r0(), [0x22, 0x00, 0x00, 0x0D, 0x60];
+        AluOp::Or, Immediate::byte(0x60), Register::rb0(), [0x21, 0x05, 0x00, 0x60, 0x00];
+        AluOp::Or, Immediate::word(0x600D), Register::r0(), [0x22, 0x05, 0x00, 0x0D, 0x60];
+        AluOp::Test, Immediate::byte(0x60), Register::rb0(), [0x21, 0x0D, 0x00, 0x60, 0x00]
+    );
+    op_test_case!(
+        alui2rp,
+        AluOp::Sub, Immediate::byte(0x60), Register::r0(), [0x23, 0x01, 0x00, 0x60, 0x00];
+        AluOp::Sub, Immediate::word(0x600D), Register::r0(), [0x24, 0x01, 0x00, 0x0D, 0x60];
+        AluOp::Xor, Immediate::byte(0x60), Register::r0(), [0x23, 0x06, 0x00, 0x60, 0x00];
+        AluOp::Xor, Immediate::word(0x600D), Register::r0(), [0x24, 0x06, 0x00, 0x0D, 0x60]
+    );
+    op_test_case!(
+        alui2ip,
+        AluOp::Adc, Immediate::byte(0x60), Immediate::word(0xF337), [0x25, 0x02, 0x00, 0x60, 0x00, 0x37, 0xF3];
+        AluOp::Adc, Immediate::word(0x600D), Immediate::word(0xF337), [0x26, 0x02, 0x00, 0x0D, 0x60, 0x37, 0xF3];
+        AluOp::Ror, Immediate::byte(0x60), Immediate::word(0xF337), [0x25, 0x0B, 0x00, 0x60, 0x00, 0x37, 0xF3];
+        AluOp::Ror, Immediate::word(0x600D), Immediate::word(0xF337), [0x26, 0x0B, 0x00, 0x0D, 0x60, 0x37, 0xF3]
+    );
+    op_test_case!(
+        aluip2r,
+        AluOp::Sbb, Immediate::word(0x600D), Register::rb0(), [0x27, 0x03, 0x00, 0x0D, 0x60];
+        AluOp::Sbb, Immediate::word(0x600D), Register::r0(), [0x28, 0x03, 0x00, 0x0D, 0x60];
+        AluOp::Sar, Immediate::word(0x600D), Register::rb0(), [0x27, 0x09, 0x00, 0x0D, 0x60];
+        AluOp::Sar, Immediate::word(0x600D), Register::r0(), [0x28, 0x09, 0x00, 0x0D, 0x60]
+    );
+    op_test_case!(
+        aluip2rp,
+        AluOp::Add, Width::Byte, Immediate::word(0x600D), Register::r0(), [0x38, 0x00, 0x00, 0x0D, 0x60];
+        AluOp::Add, Width::Word, Immediate::word(0x600D), Register::r0(), [0x39, 0x00, 0x00, 0x0D, 0x60]
+    );
+    op_test_case!(
+        aluip2ip,
+        AluOp::Add, Width::Word, Immediate::word(0x600D), Immediate::word(0xF337), [0x3B, 0x00, 0x00, 0x0D, 0x60, 0x37, 0xF3]
+    );
+    op_test_case!(
+        alur2r,
+        AluOp::Sub, Register::rb0(), Register::rb1(), [0x2B, 0x01, 0x10];
+        AluOp::Sub, Register::r0(), Register::r1(), [0x2C, 0x01, 0x10];
+        AluOp::And, Register::rb0(), Register::rb1(), [0x2B, 0x04, 0x10];
+        AluOp::And, Register::r0(), Register::r1(), [0x2C, 0x04, 0x10];
+        AluOp::Cmp, Register::r0(), Register::r1(), [0x2C, 0x0C, 0x10]
+    );
+    op_test_case!(
+        alur2rp,
+        AluOp::Sub, Register::rb0(), Register::r1(), [0x2D, 0x01, 0x10];
+        AluOp::Sub, Register::r0(), Register::r1(), [0x2E, 0x01, 0x10];
+        AluOp::Shl, Register::rb0(), Register::r1(), [0x2D, 0x07, 0x10];
+        AluOp::Shl, Register::r0(), Register::r1(), [0x2E, 0x07, 0x10]
+    );
+    op_test_case!(
+        alur2ip,
+        AluOp::Adc, Register::rb0(), Immediate::word(0x600D), [0x2F, 0x02, 0x00, 0x0D, 0x60];
+        AluOp::Adc, Register::r0(), Immediate::word(0x600D), [0x30, 0x02, 0x00, 0x0D, 0x60];
+        AluOp::Shr, Register::rb0(), Immediate::word(0x600D), [0x2F, 0x08, 0x00, 0x0D, 0x60];
+        AluOp::Shr, Register::r0(), Immediate::word(0x600D), [0x30, 0x08, 0x00, 0x0D, 0x60]
+    );
+    op_test_case!(
+        alurp2r,
+        AluOp::Sbb, Register::r0(), Register::rb1(), [0x31, 0x03, 0x10];
+        AluOp::Sbb, Register::r0(), Register::r1(), [0x32, 0x03, 0x10];
+        AluOp::Rol, Register::r0(), Register::rb1(), [0x31, 0x0A, 0x10];
+        AluOp::Rol, Register::r0(), Register::r1(), [0x32, 0x0A, 0x10]
+    );
+    op_test_case!(
+        alurp2rp,
+        AluOp::Add, Width::Byte, Register::r0(), Register::r1(), [0x3C, 0x00, 0x10]
+    );
+    op_test_case!(
+        alurp2ip,
+        AluOp::Sub, Width::Byte, Register::r0(), Immediate::word(0x600D), [0x3E, 0x01, 0x00, 0x0D, 0x60];
+        AluOp::Sub, Width::Word, Register::r0(), Immediate::word(0x600D), [0x3F, 0x01, 0x00, 0x0D, 0x60]
+    );
+    op_test_case!(
+        unaryr,
+        UnaryOp::Inc, Register::rb1(), [0x40, 0x00, 0x10];
+        UnaryOp::Inc, Register::r1(), [0x41, 0x00, 0x10]
+    );
+    op_test_case!(
+        unaryrp,
+        UnaryOp::Dec, Width::Byte, Register::r1(), [0x44, 0x01, 0x10];
+        UnaryOp::Dec, Width::Word, Register::r1(), [0x45, 0x01, 0x10]
+    );
+    op_test_case!(
+        unaryip,
+        UnaryOp::Inc, Width::Byte, Immediate::word(0xF337), [0x46, 0x00, 0x00, 0x37, 0xF3];
+        UnaryOp::Not, Width::Word, Immediate::word(0xF337), [0x47, 0x02, 0x00, 0x37, 0xF3]
+    );
+    op_test_case!(
+        jmpi,
+        Condition::Always, Immediate::word(0x600D), [0x50, 0x00, 0x00, 0x0D, 0x60];
+        Condition::LessEqual, Immediate::word(0x600D), [0x50, 0x0E, 0x00, 0x0D, 0x60]
+    );
+    op_test_case!(
+        jmpip,
+        Condition::Zero, Immediate::word(0x600D), [0x51, 0x01, 0x00, 0x0D, 0x60]
+    );
+    op_test_case!(
+        jmpr,
+        Condition::Always, Register::r5(), [0x52, 0x00, 0x05];
+        Condition::Above, Register::r5(), [0x52, 0x09, 0x05]
+    );
+    op_test_case!(
+        jmprp,
+        Condition::NotCarry, Register::r5(), [0x53, 0x04, 0x05]
+    );
+    op_test_case!(
+        muli2r,
+        MulOp::Mul, Immediate::byte(0x60), Register::rb0(), [0x70, 0x00, 0x00, 0x60, 0x00];
+        MulOp::Imul, Immediate::word(0x600D), Register::r0(), [0x71, 0x01, 0x00, 0x0D, 0x60]
+    );
+    op_test_case!(
+        mulip2r,
+        MulOp::Div, Immediate::word(0x600D), Register::rb0(), [0x72, 0x02, 0x00, 0x0D, 0x60];
+        MulOp::Idiv, Immediate::word(0x600D), Register::r0(), [0x73, 0x03, 0x00, 0x0D, 0x60]
+    );
+    op_test_case!(
+        mulr2r,
+        MulOp::Mul, Register::rb1(), Register::rb0(), [0x74, 0x00, 0x01];
+        MulOp::Div, Register::r1(), Register::r2(), [0x75, 0x02, 0x21]
+    );
+    op_test_case!(
+        mulrp2r,
+        MulOp::Imul, Register::r1(), Register::rb0(), [0x76, 0x01, 0x01];
+        MulOp::Idiv, Register::r1(), Register::r2(), [0x77, 0x03, 0x21]
+    );
+    op_test_case!(
+        movxip2r,
+        Extension::Zero, Immediate::word(0x600D), Register::r1(), [0x15, 0x00, 0x10, 0x0D, 0x60];
+        Extension::Sign, Immediate::word(0x600D), Register::r1(), [0x15, 0x01, 0x10, 0x0D, 0x60]
+    );
+    op_test_case!(
+        movxr2r,
+        Extension::Zero, Register::rb0(), Register::r1(), [0x16, 0x00, 0x10];
+        Extension::Sign, Register::rb0(), Register::r1(), [0x16, 0x01, 0x10]
+    );
+    op_test_case!(
+        movxrp2r,
+        Extension::Zero, Register::r0(), Register::r1(), [0x17, 0x00, 0x10];
+        Extension::Sign, Register::r0(), Register::r1(), [0x17, 0x01, 0x10]
+    );
+
+    #[test]
+    fn block() {
+        let cases = [
+            (BlockOp::Copy, Width::Byte, Register::r0(), [0x80, 0x00, 0x10, 0x02]),
+            (BlockOp::Fill, Width::Byte, Register::rb0(), [0x80, 0x01, 0x10, 0x02]),
+            (BlockOp::Compare, Width::Word, Register::r0(), [0x81, 0x02, 0x10, 0x02]),
+            (BlockOp::Scan, Width::Word, Register::r0(), [0x81, 0x03, 0x10, 0x02]),
+        ];
+        for (op, width, src, bytes) in cases {
+            let instr = Instruction::block(op, width, src, Register::r1(), Register::r2()).unwrap();
+            assert_eq!(instr.compile(), bytes);
+            assert_eq!(Instruction::decompile(&bytes), Ok(instr));
+        }
+
+        // The count shares its register with the destination
+        assert!(Instruction::block(BlockOp::Copy, Width::Byte, Register::r1(), Register::r2(), Register::r2()).is_err());
+        assert!(Instruction::decompile(&[0x80, 0x00, 0x21, 0x02]).is_err());
+    }
+    test_case!(
+        xchgr2r,
+        Register::rb0(), Register::rb1(), [0x90, 0x10];
+        Register::r0(), Register::r1(), [0x91, 0x10]
+    );
+    test_case!(
+        xchgr2rp,
+        Register::rb0(), Register::r1(), [0x92, 0x10];
+        Register::r0(), Register::r1(), [0x93, 0x10]
+    );
+    test_case!(
+        xchgr2ip,
+        Register::rb0(), Immediate::word(0x8000), [0x94, 0x00, 0x00, 0x80];
+        Register::r0(), Immediate::word(0x8000), [0x95, 0x00, 0x00, 0x80]
+    );
+    test_case!(
+        cmpxchgr2r,
+        Register::rb0(), Register::rb1(), [0x98, 0x10];
+        Register::r0(), Register::r1(), [0x99, 0x10]
+    );
+    test_case!(
+        cmpxchgr2rp,
+        Register::rb0(), Register::r1(), [0x9A, 0x10];
+        Register::r0(), Register::r1(), [0x9B, 0x10]
+    );
+    test_case!(
+        cmpxchgr2ip,
+        Register::rb0(), Immediate::word(0x8000), [0x9C, 0x00, 0x00, 0x80];
+        Register::r0(), Immediate::word(0x8000), [0x9D, 0x00, 0x00, 0x80]
+    );
+    test_case!(
+        movi2m,
+        Immediate::word(0x600D), Address::new(Register::r0(), Some((Register::r1(), 2)), 0x0004), [0xA1, 0x00, 0x10, 0x05, 0x0D, 0x60, 0x04, 0x00]
+    );
+    test_case!(
+        movm2r,
+        Address::new(Register::r2(), None, 0xF337), Register::rb1(), [0xA2, 0x10, 0x02, 0x00, 0x37, 0xF3]
+    );
+    op_test_case!(
+        alui2m,
+        AluOp::Add, Immediate::byte(0x60), Address::new(Register::r3(), Some((Register::r4(), 8)), 0), [0xA8, 0x00, 0x00, 0x43, 0x07, 0x60, 0x00, 0x00, 0x00]
+    );
+    op_test_case!(
+        jmprel,
+        Condition::Less, Immediate::word(0xFFF8), [0xB8, 0x0D, 0x00, 0xF8, 0xFF]
+    );
+    test_case!(callrel, Immediate::word(0x0002), [0xB9, 0x00, 0x02, 0x00]);
+    test_case!(
+        movrel2r,
+        Immediate::word(0x000C), Register::r3(), [0xBA, 0x30, 0x0C, 0x00]
+    );
+    test_case!(
+        ini,
+        Immediate::byte(0x10), Register::rb0(), [0x66, 0x00, 0x10, 0x00];
+        Immediate::byte(0x10), Register::r1(), [0x67, 0x10, 0x10, 0x00]
+    );
+    test_case!(
+        outr,
+        Register::rb2(), Register::rb3(), [0x6C, 0x32];
+        Register::r2(), Register::rb3(), [0x6D, 0x32]
+    );
+
+    #[test]
+    fn len() {
+        let instrs = [
+            Instruction::nop().unwrap(),
+            Instruction::movr2r(Register::r0(), Register::r1()).unwrap(),
+            Instruction::alui2ip(AluOp::Add, Immediate::byte(0x60), Immediate::word(0xF337)).unwrap(),
+            Instruction::block(BlockOp::Copy, Width::Byte, Register::r0(), Register::r1(), Register::r2()).unwrap(),
+            Instruction::alui2m(AluOp::Add, Immediate::word(0x600D), Address::new(Register::r0(), Some((Register::r1(), 4)), 0x0010)).unwrap(),
+        ];
+        for instr in instrs {
+            assert_eq!(instr.len() as usize, instr.compile().len(), "{instr:?}");
+            assert_eq!(Instruction::len_of(instr.opcode()), Some(instr.len()), "{instr:?}");
+        }
+        assert_eq!(Instruction::MAX_LEN, 9);
+        assert_eq!(Instruction::len_of(0xFF), None);
+    }
+
+    #[test]
+    fn encode() {
+        let instr = Instruction::alui2m(AluOp::Add, Immediate::word(0x600D), Address::new(Register::r0(), Some((Register::r1(), 4)), 0x0010)).unwrap();
+
+        // Leftover bytes are untouched
+        let mut bytes = [0xFF; 16];
+        assert_eq!(instr.encode(&mut bytes), Ok(9));
+        assert_eq!(bytes[..9], instr.compile());
+        assert_eq!(bytes[9..], [0xFF; 7]);
+
+        assert_eq!(instr.encode(&mut bytes[..8]), Err(Error::BufferTooSmall(8, 9)));
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn encode_to() {
+        let instr = Instruction::alui2m(AluOp::Add, Immediate::word(0x600D), Address::new(Register::r0(), Some((Register::r1(), 4)), 0x0010)).unwrap();
+        let mut written = vec![0xAA];
+        assert_eq!(instr.encode_to(&mut written), Ok(9));
+        assert_eq!(written[1..], instr.compile());
+    }
 }
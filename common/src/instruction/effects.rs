@@ -0,0 +1,290 @@
+#[allow(unused_imports)]
+use crate::prelude::*;
+
+/// How an instruction uses a memory operand
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Access {
+    Read,
+    Write,
+    ReadWrite,
+}
+
+impl Access {
+    fn new(read : bool, write : bool) -> Self {
+        match (read, write) {
+            (true, true) => Self::ReadWrite,
+            (false, true) => Self::Write,
+            _ => Self::Read,
+        }
+    }
+}
+
+/// A memory operand and the width of the data accessed through it
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MemoryAccess {
+    pub operand : Operand,
+    pub width : Width,
+    pub access : Access,
+}
+
+impl MemoryAccess {
+    fn new(operand : &Operand, width : Width, read : bool, write : bool) -> Option<Self> {
+        operand.is_memory().then(|| Self { operand : *operand, width, access : Access::new(read, write) })
+    }
+}
+
+impl Instruction {
+    /// Whether the destination of a binary operation is read before it is written
+    fn reads_dest(op : &BinaryOp) -> bool {
+        !matches!(op, BinaryOp::Mov | BinaryOp::Movx(_))
+    }
+
+    /// Whether the destination of a binary operation is written
+    fn writes_dest(op : &BinaryOp) -> bool {
+        !matches!(op, BinaryOp::Alu(op) if !op.stores_result())
+    }
+
+    /// Registers whose value it uses, including the ones that make up addresses
+    pub fn reads(&self) -> Vec<Register> {
+        let regs = match self {
+            Self::Nop | Self::Hlt | Self::Wfi => vec![],
+            Self::Binary(op, src, dest) | Self::BinaryMem(op, _, src, dest) => {
+                let mut regs = src.read_registers();
+                regs.extend(match (op, dest) {
+                    // Byte products and dividends take the whole word register
+                    (BinaryOp::Mul(_), Operand::Reg(dest)) => vec![dest.with_width(Width::Word)],
+                    _ if Self::reads_dest(op) => dest.read_registers(),
+                    _ => dest.address_registers(),
+                });
+                match op {
+                    BinaryOp::Alu(AluOp::Adc | AluOp::Sbb) => regs.push(Register::flags()),
+                    BinaryOp::Cmpxchg => regs.extend(self.data_width().map(|width| Register::r0().with_width(width))),
+                    BinaryOp::Mul(MulOp::Div | MulOp::Idiv) => regs.extend(Self::mul_high(dest)),
+                    _ => (),
+                }
+                regs
+            },
+            Self::Unary(_, dest) | Self::UnaryMem(_, _, dest) => dest.read_registers(),
+            Self::Jmp(Condition::Always, target) => target.read_registers(),
+            Self::Jmp(_, target) => [target.read_registers(), vec![Register::flags()]].concat(),
+            Self::Call(target) => [target.read_registers(), vec![Register::rip(), Register::rsh()]].concat(),
+            Self::Ret | Self::Iret => vec![Register::rsh(), Register::rsb()],
+            Self::Int(_) => vec![Register::flags(), Register::rip(), Register::rsh()],
+            Self::Sti | Self::Cli => vec![Register::flags()],
+            Self::Push(src) => [src.read_registers(), vec![Register::rsh()]].concat(),
+            Self::Pop(dest) => [dest.address_registers(), vec![Register::rsh(), Register::rsb()]].concat(),
+            Self::In(port, _) => port.read_registers(),
+            Self::Out(src, port) => [vec![*src], port.read_registers()].concat(),
+            Self::Block(_, _, src, dest, count) => vec![*src, *dest, *count],
+        };
+        Self::unique(regs)
+    }
+
+    /// Registers it may change, including the flags
+    pub fn writes(&self) -> Vec<Register> {
+        let reg = |operand : &Operand| match operand {
+            Operand::Reg(reg) => vec![*reg],
+            _ => vec![],
+        };
+
+        let regs = match self {
+            Self::Nop | Self::Hlt | Self::Wfi | Self::Out(_, _) => vec![],
+            Self::Binary(op, src, dest) | Self::BinaryMem(op, _, src, dest) => match op {
+                BinaryOp::Mov | BinaryOp::Movx(_) => reg(dest),
+                BinaryOp::Xchg => [reg(src), reg(dest)].concat(),
+                BinaryOp::Cmpxchg => [reg(dest), self.data_width().map(|width| Register::r0().with_width(width)).into_iter().collect(), vec![Register::flags()]].concat(),
+                BinaryOp::Alu(op) if op.stores_result() => [reg(dest), vec![Register::flags()]].concat(),
+                BinaryOp::Alu(_) => vec![Register::flags()],
+                BinaryOp::Mul(op) => {
+                    let low = reg(dest).into_iter().map(|dest| dest.with_width(Width::Word));
+                    // Divisions leave the flags alone
+                    let flags = matches!(op, MulOp::Mul | MulOp::Imul).then(Register::flags);
+                    low.chain(Self::mul_high(dest)).chain(flags).collect()
+                },
+            },
+            Self::Unary(_, dest) | Self::UnaryMem(_, _, dest) => [reg(dest), vec![Register::flags()]].concat(),
+            Self::Jmp(_, _) => vec![Register::rip()],
+            Self::Call(_) | Self::Ret => vec![Register::rip(), Register::rsh()],
+            Self::Int(_) | Self::Iret => vec![Register::rip(), Register::rsh(), Register::flags()],
+            Self::Sti | Self::Cli => vec![Register::flags()],
+            Self::Push(_) => vec![Register::rsh()],
+            Self::Pop(dest) => [reg(dest), vec![Register::rsh()]].concat(),
+            Self::In(_, dest) => vec![*dest],
+            Self::Block(op, _, src, dest, count) => {
+                let mut regs = if op.has_src() { vec![*src, *dest, *count] } else { vec![*dest, *count] };
+                if matches!(op, BlockOp::Compare | BlockOp::Scan) {
+                    regs.push(Register::flags());
+                }
+                regs
+            },
+        };
+        Self::unique(regs)
+    }
+
+    /// Keeps the first occurrence of each register, wherever the others are
+    fn unique(regs : Vec<Register>) -> Vec<Register> {
+        let mut unique = Vec::with_capacity(regs.len());
+        for reg in regs {
+            if !unique.contains(&reg) {
+                unique.push(reg);
+            }
+        }
+        unique
+    }
+
+    /// The register holding the high half of a word multiplication or division
+    fn mul_high(dest : &Operand) -> Option<Register> {
+        match dest {
+            Operand::Reg(dest @ Register::R(Width::Word, _)) => dest.next(),
+            _ => None,
+        }
+    }
+
+    /// Memory operands it reads or writes, not counting the stack.
+    /// Block operations access the elements their pointers point to, one after the other
+    pub fn memory(&self) -> Vec<MemoryAccess> {
+        let word = |operand : &Operand| MemoryAccess::new(operand, Width::Word, true, false);
+        let accesses = match self {
+            Self::Binary(op, src, dest) | Self::BinaryMem(op, _, src, dest) => {
+                let width = self.data_width().unwrap_or(Width::Byte);
+                let src_width = if matches!(op, BinaryOp::Movx(_)) { Width::Byte } else { width };
+                vec![
+                    MemoryAccess::new(src, src_width, true, *op == BinaryOp::Xchg),
+                    MemoryAccess::new(dest, width, Self::reads_dest(op), Self::writes_dest(op)),
+                ]
+            },
+            Self::UnaryMem(_, width, dest) => vec![MemoryAccess::new(dest, *width, true, true)],
+            Self::Jmp(_, target) | Self::Call(target) => vec![word(target)],
+            Self::Push(src) => vec![word(src)],
+            Self::Pop(dest) => vec![MemoryAccess::new(dest, Width::Word, false, true)],
+            Self::Block(op, width, src, dest, _) => {
+                let src = op.has_src().then(|| MemoryAccess::new(&Operand::RegPtr(*src), *width, true, false)).flatten();
+                let compares = matches!(op, BlockOp::Compare | BlockOp::Scan);
+                vec![src, MemoryAccess::new(&Operand::RegPtr(*dest), *width, compares, !compares)]
+            },
+            _ => vec![],
+        };
+        accesses.into_iter().flatten().collect()
+    }
+
+    /// Pushes or pops the stack
+    pub fn uses_stack(&self) -> bool {
+        matches!(self, Self::Call(_) | Self::Ret | Self::Int(_) | Self::Iret | Self::Push(_) | Self::Pop(_))
+    }
+
+    pub fn touches_memory(&self) -> bool {
+        self.uses_stack() || !self.memory().is_empty()
+    }
+
+    /// Whether it may continue somewhere other than the next instruction
+    pub fn changes_rip(&self) -> bool {
+        self.writes().contains(&Register::rip())
+    }
+
+    /// Nominal cost: one cycle to execute, one for each memory access (two if it is read and written),
+    /// one for each value pushed or popped and extra ones for multiplications and divisions.
+    /// Block operations cost this for every element
+    pub fn cycles(&self) -> u32 {
+        let memory : u32 = self.memory().iter()
+            .map(|access| if access.access == Access::ReadWrite { 2 } else { 1 })
+            .sum();
+        let extra = match self {
+            Self::Binary(BinaryOp::Mul(MulOp::Mul | MulOp::Imul), _, _) => 3,
+            Self::Binary(BinaryOp::Mul(MulOp::Div | MulOp::Idiv), _, _) => 7,
+            Self::Call(_) | Self::Ret | Self::Push(_) | Self::Pop(_) => 1,
+            Self::Iret => 2,
+            // Also reads the handler from the vector table
+            Self::Int(_) => 3,
+            _ => 0,
+        };
+        1 + memory + extra
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn registers() {
+        let instr = Instruction::alur2r(AluOp::Adc, Register::r1(), Register::r2()).unwrap();
+        assert_eq!(instr.reads(), vec![Register::r1(), Register::r2(), Register::flags()]);
+        assert_eq!(instr.writes(), vec![Register::r2(), Register::flags()]);
+
+        let instr = Instruction::movm2r(Address::new(Register::r0(), Some((Register::r1(), 2)), 0x10), Register::rb3()).unwrap();
+        assert_eq!(instr.reads(), vec![Register::r0(), Register::r1()]);
+        assert_eq!(instr.writes(), vec![Register::rb3()]);
+
+        let instr = Instruction::mulr2r(MulOp::Div, Register::r4(), Register::r1()).unwrap();
+        assert_eq!(instr.reads(), vec![Register::r4(), Register::r1(), Register::r2()]);
+        assert_eq!(instr.writes(), vec![Register::r1(), Register::r2()]);
+
+        // The dividend and the remainder span the whole word register
+        let instr = Instruction::mulr2r(MulOp::Div, Register::rb1(), Register::rb0()).unwrap();
+        assert_eq!(instr.reads(), vec![Register::rb1(), Register::r0()]);
+        assert_eq!(instr.writes(), vec![Register::r0()]);
+
+        let instr = Instruction::mulr2r(MulOp::Mul, Register::rb1(), Register::rb0()).unwrap();
+        assert_eq!(instr.reads(), vec![Register::rb1(), Register::r0()]);
+        assert_eq!(instr.writes(), vec![Register::r0(), Register::flags()]);
+
+        let instr = Instruction::alum2r(AluOp::Add, Address::new(Register::r0(), Some((Register::r0(), 2)), 0), Register::r0()).unwrap();
+        assert_eq!(instr.reads(), vec![Register::r0()]);
+
+        let instr = Instruction::cmpxchgr2rp(Register::rb1(), Register::r2()).unwrap();
+        assert_eq!(instr.reads(), vec![Register::rb1(), Register::r2(), Register::rb0()]);
+        assert_eq!(instr.writes(), vec![Register::rb0(), Register::flags()]);
+
+        let instr = Instruction::block(BlockOp::Copy, Width::Byte, Register::r0(), Register::r1(), Register::r2()).unwrap();
+        assert_eq!(instr.writes(), vec![Register::r0(), Register::r1(), Register::r2()]);
+    }
+
+    #[test]
+    fn memory() {
+        let instr = Instruction::alui2rp(AluOp::Add, Immediate::word(0x600D), Register::r0()).unwrap();
+        assert_eq!(instr.memory(), vec![MemoryAccess { operand : Operand::RegPtr(Register::r0()), width : Width::Word, access : Access::ReadWrite }]);
+
+        let instr = Instruction::movip2rp(Width::Byte, Immediate::word(0xF337), Register::r1()).unwrap();
+        assert_eq!(instr.memory(), vec![
+            MemoryAccess { operand : Operand::ImmPtr(Immediate::word(0xF337)), width : Width::Byte, access : Access::Read },
+            MemoryAccess { operand : Operand::RegPtr(Register::r1()), width : Width::Byte, access : Access::Write },
+        ]);
+
+        let instr = Instruction::alui2ip(AluOp::Cmp, Immediate::byte(0x60), Immediate::word(0xF337)).unwrap();
+        assert_eq!(instr.memory()[0].access, Access::Read);
+
+        assert!(Instruction::pushr(Register::r0()).unwrap().touches_memory());
+        assert!(!Instruction::movr2r(Register::r0(), Register::r1()).unwrap().touches_memory());
+    }
+
+    #[test]
+    fn effective_address() {
+        let regs = |reg : &Register| match reg {
+            Register::RIP => 0x8010,
+            Register::R(_, n) => 0x1000 * *n as u16,
+            _ => 0,
+        };
+        let addr = Operand::Indexed(Address::new(Register::r1(), Some((Register::r2(), 4)), 0xFFFF));
+        assert_eq!(addr.effective_address(regs), Some(0x8FFF));
+        assert_eq!(Operand::Indexed(Address::rip_relative(0x20)).effective_address(regs), Some(0x8030));
+        assert_eq!(Operand::RegPtr(Register::r3()).effective_address(regs), Some(0x3000));
+        assert_eq!(Operand::Reg(Register::r3()).effective_address(regs), None);
+    }
+
+    #[test]
+    fn changes_rip() {
+        assert!(Instruction::jmpi(Condition::Zero, Immediate::word(0x8000)).unwrap().changes_rip());
+        assert!(Instruction::ret().unwrap().changes_rip());
+        assert!(Instruction::movi2r(Immediate::word(0x8000), Register::rip()).unwrap().changes_rip());
+        assert!(!Instruction::cmpxchgr2r(Register::r1(), Register::r2()).unwrap().changes_rip());
+    }
+
+    #[test]
+    fn cycles() {
+        assert_eq!(Instruction::nop().unwrap().cycles(), 1);
+        assert_eq!(Instruction::movr2rp(Register::r0(), Register::r1()).unwrap().cycles(), 2);
+        assert_eq!(Instruction::unaryip(UnaryOp::Inc, Width::Word, Immediate::word(0x8000)).unwrap().cycles(), 3);
+        assert_eq!(Instruction::mulr2r(MulOp::Idiv, Register::r0(), Register::r1()).unwrap().cycles(), 8);
+        assert_eq!(Instruction::callrp(Register::r0()).unwrap().cycles(), 3);
+    }
+}
@@ -1,25 +1,22 @@
 use crate::prelude::*;
 mod compile;
 mod decompile;
-
-#[derive(Debug, Clone, PartialEq)]
-pub enum Instruction {
-    Nop,
-    MovI2R(Immediate, Register),
-    MovI2RP(Immediate, Register),
-    MovI2IP(Immediate, Immediate),
-    MovIP2R(Immediate, Register),
-    MovIP2RP(Immediate, Register),
-    MovIP2IP(Immediate, Immediate),
-    MovR2R(Register, Register),
-    MovR2RP(Register, Register),
-    MovR2IP(Register, Immediate),
-    MovRP2R(Register, Register),
-    MovRP2RP(Register, Register),
-    MovRP2IP(Register, Immediate),
-}
+mod effects;
+mod operand;
+mod ops;
+#[cfg(feature = "std")]
+mod stream;
+mod table;
+mod text;
+pub use effects::*;
+pub use operand::*;
+pub use ops::*;
+#[cfg(feature = "std")]
+pub use stream::*;
+pub use table::*;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum ParamIdx {
     SrcReg,
     DestReg,
@@ -27,43 +24,107 @@ pub enum ParamIdx {
     SecondImm,
 }
 
-macro_rules! instruction_constructor {
-    ($ident:ident, $IDENT:ident) => {
-        pub fn $ident() -> Result<Self> {
-            Ok(Self::$IDENT)
-        }
-    };
+/// Operands are ordered source first, then destination
+#[derive(Debug, Clone, PartialEq)]
+pub enum Instruction {
+    Nop,
+    Binary(BinaryOp, Operand, Operand),
+    /// Both operands are in memory, which doesn't know the width of the data, so it is explicit
+    BinaryMem(BinaryOp, Width, Operand, Operand),
+    Unary(UnaryOp, Operand),
+    /// The operand is in memory, so the width of the data is explicit
+    UnaryMem(UnaryOp, Width, Operand),
+    Jmp(Condition, Operand),
+    Call(Operand),
+    Ret,
+    Int(Immediate),
+    Iret,
+    Sti,
+    Cli,
+    Hlt,
+    Wfi,
+    Push(Operand),
+    Pop(Operand),
+    /// Reads a port, which is a byte immediate or register, into a register
+    In(Operand, Register),
+    /// Writes a register into a port
+    Out(Register, Operand),
+    /// Source or value, destination and count
+    Block(BlockOp, Width, Register, Register, Register),
+}
 
-    ($ident:ident, $IDENT:ident, $param:ident) => {
-        pub fn $ident(param : $param) -> Result<Self> {
-            let res = Self::$IDENT(param);
-            res.check_valid()
-        }
-    };
+impl Instruction {
+    pub fn binary(op : BinaryOp, src : Operand, dest : Operand) -> Result<Self> {
+        Self::Binary(op, src, dest).check_valid()
+    }
+
+    pub fn binary_mem(op : BinaryOp, width : Width, src : Operand, dest : Operand) -> Result<Self> {
+        Self::BinaryMem(op, width, src, dest).check_valid()
+    }
+
+    pub fn unary(op : UnaryOp, dest : Operand) -> Result<Self> {
+        Self::Unary(op, dest).check_valid()
+    }
+
+    pub fn unary_mem(op : UnaryOp, width : Width, dest : Operand) -> Result<Self> {
+        Self::UnaryMem(op, width, dest).check_valid()
+    }
+
+    pub fn jmp(cond : Condition, target : Operand) -> Result<Self> {
+        Self::Jmp(cond, target).check_valid()
+    }
 
-    ($ident:ident, $IDENT:ident, $left:ident, $right:ident) => {
-        pub fn $ident(left : $left, right : $right) -> Result<Self> {
-            let res = Self::$IDENT(left, right);
-            res.check_valid()
+    pub fn call(target : Operand) -> Result<Self> {
+        Self::Call(target).check_valid()
+    }
+
+    pub fn push(src : Operand) -> Result<Self> {
+        Self::Push(src).check_valid()
+    }
+
+    pub fn pop(dest : Operand) -> Result<Self> {
+        Self::Pop(dest).check_valid()
+    }
+
+    pub fn input(port : Operand, dest : Register) -> Result<Self> {
+        Self::In(port, dest).check_valid()
+    }
+
+    pub fn output(src : Register, port : Operand) -> Result<Self> {
+        Self::Out(src, port).check_valid()
+    }
+
+    pub fn mnemonic(&self) -> Mnemonic {
+        match self {
+            Self::Nop => Mnemonic::Nop,
+            Self::Binary(op, _, _) | Self::BinaryMem(op, _, _, _) => Mnemonic::binary(*op),
+            Self::Unary(op, _) | Self::UnaryMem(op, _, _) => Mnemonic::Unary(*op),
+            Self::Jmp(cond, _) => Mnemonic::Jmp(*cond),
+            Self::Call(_) => Mnemonic::Call,
+            Self::Ret => Mnemonic::Ret,
+            Self::Int(_) => Mnemonic::Int,
+            Self::Iret => Mnemonic::Iret,
+            Self::Sti => Mnemonic::Sti,
+            Self::Cli => Mnemonic::Cli,
+            Self::Hlt => Mnemonic::Hlt,
+            Self::Wfi => Mnemonic::Wfi,
+            Self::Push(_) => Mnemonic::Push,
+            Self::Pop(_) => Mnemonic::Pop,
+            Self::In(_, _) => Mnemonic::In,
+            Self::Out(_, _) => Mnemonic::Out,
+            Self::Block(op, width, _, _, _) => Mnemonic::Block(*op, *width),
         }
-    };
-}
+    }
 
-impl Instruction {
-    instruction_constructor!(nop, Nop);
-
-    instruction_constructor!(movi2r, MovI2R, Immediate, Register);
-    instruction_constructor!(movi2rp, MovI2RP, Immediate, Register);
-    instruction_constructor!(movi2ip, MovI2IP, Immediate, Immediate);
-    instruction_constructor!(movip2r, MovIP2R, Immediate, Register);
-    instruction_constructor!(movip2rp, MovIP2RP, Immediate, Register);
-    instruction_constructor!(movip2ip, MovIP2IP, Immediate, Immediate);
-    instruction_constructor!(movr2r, MovR2R, Register, Register);
-    instruction_constructor!(movr2rp, MovR2RP, Register, Register);
-    instruction_constructor!(movr2ip, MovR2IP, Register, Immediate);
-    instruction_constructor!(movrp2r, MovRP2R, Register, Register);
-    instruction_constructor!(movrp2rp, MovRP2RP, Register, Register);
-    instruction_constructor!(movrp2ip, MovRP2IP, Register, Immediate);
+    /// Width of the data a binary or unary operation works on, which is explicit when it is all in memory
+    pub fn data_width(&self) -> Option<Width> {
+        match self {
+            Self::Binary(_, src, dest) => src.width().or(dest.width()),
+            Self::Unary(_, dest) => dest.width(),
+            Self::BinaryMem(_, width, _, _) | Self::UnaryMem(_, width, _) => Some(*width),
+            _ => None,
+        }
+    }
 
     fn check_valid(self) -> Result<Self> {
         if self.is_valid() {
@@ -73,66 +134,29 @@ impl Instruction {
         }
     }
 
-    fn is_valid(&self) -> bool {
-        use Instruction::*;
-        match self {
-            Nop => true,
-
-            MovI2R(src, dest) => src.width() == dest.width(),
-            MovI2RP(_, dest) => dest.width() == Width::Word,
-            MovI2IP(_, dest) => dest.width() == Width::Word,
-            MovIP2R(src, _) => src.width() == Width::Word,
-            MovIP2RP(src, dest) => src.width() == Width::Word && dest.width() == Width::Word,
-            MovIP2IP(src, dest) => src.width() == Width::Word && dest.width() == Width::Word,
-            MovR2R(src, dest) => src.width() == dest.width(),
-            MovR2RP(_, dest) => dest.width() == Width::Word,
-            MovR2IP(_, dest) => dest.width() == Width::Word,
-            MovRP2R(src, _) => src.width() == Width::Word,
-            MovRP2RP(src, dest) => src.width() == Width::Word && dest.width() == Width::Word,
-            MovRP2IP(src, dest) => src.width() == Width::Word && dest.width() == Width::Word,
+    fn is_wide_dest(dest : &Register) -> bool {
+        match dest {
+            Register::R(Width::Byte, _) => true,
+            Register::R(Width::Word, _) => dest.next().is_some(),
+            _ => false,
         }
     }
 
-    #[allow(dead_code, clippy::len_without_is_empty)]
-    pub fn len(&self) -> u16 {
-        self.compile().len() as u16
+    /// Stepping the pointers must leave the count alone, or it might never reach 0
+    fn is_own_count(count : &Register, src : &Register, dest : &Register) -> bool {
+        count.as_src() != src.as_src() && count.as_src() != dest.as_src()
     }
 
+    /// The second immediate is the last one, which is also the first when there is only one
     pub fn replace_imm(self, param_idx : ParamIdx, new_value : u16) -> Result<Self> {
+        let count = self.imm_count();
         match param_idx {
-            ParamIdx::FirstImm => self.replace_first_imm(new_value),
-            ParamIdx::SecondImm => self.replace_second_imm(new_value),
+            _ if count == 0 => panic!("{self:?}"), // TODO: Error
+            ParamIdx::FirstImm => self.replace_nth_imm(0, new_value),
+            ParamIdx::SecondImm => self.replace_nth_imm(count - 1, new_value),
             _ => unreachable!("{param_idx:?}"), // TODO: Error
         }
     }
-
-    pub fn replace_first_imm(self, new_value : u16) -> Result<Self> {
-        use Instruction::*;
-        match self {
-            MovI2R(src, dest) => Self::movi2r(Immediate::new_unchecked(src.width(), new_value), dest),
-            MovI2RP(src, dest) => Self::movip2r(Immediate::new_unchecked(src.width(), new_value), dest),
-            MovI2IP(src, dest) => Self::movi2ip(Immediate::new_unchecked(src.width(), new_value), dest),
-            MovIP2R(src, dest) => Self::movip2r(Immediate::new_unchecked(src.width(), new_value), dest),
-            MovIP2RP(src, dest) => Self::movip2rp(Immediate::new_unchecked(src.width(), new_value), dest),
-            MovIP2IP(src, dest) => Self::movip2ip(Immediate::new_unchecked(src.width(), new_value), dest),
-            MovR2IP(src, dest) => Self::movr2ip(src, Immediate::new_unchecked(dest.width(), new_value)),
-            MovRP2IP(src, dest) => Self::movrp2ip(src, Immediate::new_unchecked(dest.width(), new_value)),
-
-            _ => panic!(), // TODO: Error
-        }
-    }
-
-    pub fn replace_second_imm(self, new_value : u16) -> Result<Self> {
-        use Instruction::*;
-        match self {
-            MovI2IP(src, dest) => Self::movi2ip(src, Immediate::new_unchecked(dest.width(), new_value)),
-            MovIP2IP(src, dest) => Self::movip2ip(src, Immediate::new_unchecked(dest.width(), new_value)),
-            MovR2IP(src, dest) => Self::movr2ip(src, Immediate::new_unchecked(dest.width(), new_value)),
-            MovRP2IP(src, dest) => Self::movrp2ip(src, Immediate::new_unchecked(dest.width(), new_value)),
-
-            _ => panic!("{self:?}"), // TODO: Error
-        }
-    }
 }
 
 #[cfg(test)]
@@ -162,6 +186,29 @@ mod test {
         };
     }
 
+    macro_rules! alu_test_case {
+        (
+            $name:ident($op:expr),
+            $($l_ok:expr, $r_ok:expr),* ;
+            $($l_err:expr, $r_err:expr),*
+        ) => {
+            #[test]
+            fn $name() {
+                $(
+                    let left = $l_ok; let right = $r_ok;
+                    let instr = Instruction::$name($op, left, right);
+                    assert!(instr.is_ok(), "{instr:?}");
+                )*
+
+                $(
+                    let left = $l_err; let right = $r_err;
+                    let instr = Instruction::$name($op, left, right);
+                    assert!(instr.is_err(), "{instr:?}");
+                )*
+            }
+        };
+    }
+
     test_case!(nop,;);
 
     // Mov
@@ -189,8 +236,8 @@ mod test {
         Immediate::byte(0x60), Immediate::byte(0xF3),
         Immediate::word(0x600D), Immediate::byte(0xF3)
     );
-    test_case!(
-        movip2ip,
+    alu_test_case!(
+        movip2ip(Width::Word),
         Immediate::word(0x600D), Immediate::word(0xF337)
         ;
         Immediate::byte(0x60), Immediate::word(0xF337),
@@ -228,11 +275,290 @@ mod test {
         Register::rb0(), Register::r1(),
         Register::rb0(), Register::rb1()
     );
-    test_case!(
-        movrp2rp,
+    alu_test_case!(
+        movrp2rp(Width::Byte),
         Register::r0(), Register::r1()
         ;
         Register::rb0(), Register::r1(),
         Register::rb0(), Register::rb1()
     );
+
+    // Alu
+    alu_test_case!(
+        alui2r(AluOp::Add),
+        Immediate::byte(0x60), Register::rb0(),
+        Immediate::word(0x600D), Register::r0()
+        ;
+        Immediate::byte(0x60), Register::r0(),
+        Immediate::word(0x600D), Register::rb0()
+    );
+    alu_test_case!(
+        alui2ip(AluOp::Sub),
+        Immediate::byte(0x60), Immediate::word(0xF337),
+        Immediate::word(0x600D), Immediate::word(0xF337)
+        ;
+        Immediate::word(0x600D), Immediate::byte(0xF3)
+    );
+    alu_test_case!(
+        alur2r(AluOp::Adc),
+        Register::rb0(), Register::rb1(),
+        Register::r0(), Register::r1()
+        ;
+        Register::rb0(), Register::r1(),
+        Register::r0(), Register::rb1()
+    );
+    #[test]
+    fn alurp2rp() {
+        assert!(Instruction::alurp2rp(AluOp::Sbb, Width::Byte, Register::r0(), Register::r1()).is_ok());
+        assert!(Instruction::alurp2rp(AluOp::Sbb, Width::Word, Register::r0(), Register::r1()).is_ok());
+        assert!(Instruction::alurp2rp(AluOp::Sbb, Width::Word, Register::rb0(), Register::r1()).is_err());
+        assert!(Instruction::alurp2rp(AluOp::Sbb, Width::Byte, Register::r0(), Register::rb1()).is_err());
+    }
+
+    // Jmp
+    #[test]
+    fn jmp() {
+        assert!(Instruction::jmpi(Condition::Always, Immediate::word(0x600D)).is_ok());
+        assert!(Instruction::jmpi(Condition::Zero, Immediate::byte(0x60)).is_err());
+        assert!(Instruction::jmpip(Condition::NotZero, Immediate::word(0x600D)).is_ok());
+        assert!(Instruction::jmpip(Condition::Carry, Immediate::byte(0x60)).is_err());
+        assert!(Instruction::jmpr(Condition::Less, Register::r0()).is_ok());
+        assert!(Instruction::jmpr(Condition::Greater, Register::rb0()).is_err());
+        assert!(Instruction::jmprp(Condition::Above, Register::r0()).is_ok());
+        assert!(Instruction::jmprp(Condition::BelowEqual, Register::rb0()).is_err());
+    }
+
+    // Call
+    #[test]
+    fn call() {
+        assert!(Instruction::calli(Immediate::word(0x600D)).is_ok());
+        assert!(Instruction::calli(Immediate::byte(0x60)).is_err());
+        assert!(Instruction::callip(Immediate::word(0x600D)).is_ok());
+        assert!(Instruction::callip(Immediate::byte(0x60)).is_err());
+        assert!(Instruction::callr(Register::r0()).is_ok());
+        assert!(Instruction::callr(Register::rb0()).is_err());
+        assert!(Instruction::callrp(Register::r0()).is_ok());
+        assert!(Instruction::callrp(Register::rb0()).is_err());
+    }
+
+    // Interrupts
+    #[test]
+    fn int() {
+        assert!(Instruction::int(Immediate::byte(0x21)).is_ok());
+        assert!(Instruction::int(Immediate::word(0x21)).is_err());
+    }
+
+    // Xchg
+    test_case!(
+        xchgr2r,
+        Register::rb0(), Register::rb1(),
+        Register::r0(), Register::r1()
+        ;
+        Register::rb0(), Register::r1(),
+        Register::r0(), Register::rb1()
+    );
+    test_case!(
+        xchgr2rp,
+        Register::rb0(), Register::r1(),
+        Register::r0(), Register::r1()
+        ;
+        Register::r0(), Register::rb1()
+    );
+    test_case!(
+        xchgr2ip,
+        Register::rb0(), Immediate::word(0x8000),
+        Register::r0(), Immediate::word(0x8000)
+        ;
+        Register::r0(), Immediate::byte(0x80)
+    );
+    test_case!(
+        cmpxchgr2r,
+        Register::rb0(), Register::rb1(),
+        Register::r0(), Register::r1()
+        ;
+        Register::rb0(), Register::r1()
+    );
+    test_case!(
+        cmpxchgr2rp,
+        Register::rb0(), Register::r1()
+        ;
+        Register::r0(), Register::rb1()
+    );
+    test_case!(
+        cmpxchgr2ip,
+        Register::r0(), Immediate::word(0x8000)
+        ;
+        Register::r0(), Immediate::byte(0x80)
+    );
+
+    // Movx
+    alu_test_case!(
+        movxip2r(Extension::Zero),
+        Immediate::word(0x600D), Register::r0()
+        ;
+        Immediate::byte(0x60), Register::r0(),
+        Immediate::word(0x600D), Register::rb0()
+    );
+    alu_test_case!(
+        movxr2r(Extension::Sign),
+        Register::rb0(), Register::r1(),
+        Register::rb0(), Register::r0()
+        ;
+        Register::r0(), Register::r1(),
+        Register::rb0(), Register::rb1(),
+        Register::r0(), Register::rb1()
+    );
+    alu_test_case!(
+        movxrp2r(Extension::Zero),
+        Register::r0(), Register::r1()
+        ;
+        Register::rb0(), Register::r1(),
+        Register::r0(), Register::rb1()
+    );
+
+    // Block
+    #[test]
+    fn block() {
+        assert!(Instruction::block(BlockOp::Copy, Width::Byte, Register::r0(), Register::r1(), Register::r2()).is_ok());
+        assert!(Instruction::block(BlockOp::Compare, Width::Word, Register::r0(), Register::r1(), Register::r2()).is_ok());
+        assert!(Instruction::block(BlockOp::Copy, Width::Byte, Register::rb0(), Register::r1(), Register::r2()).is_err());
+        assert!(Instruction::block(BlockOp::Fill, Width::Byte, Register::rb0(), Register::r1(), Register::r2()).is_ok());
+        assert!(Instruction::block(BlockOp::Scan, Width::Word, Register::r0(), Register::r1(), Register::r2()).is_ok());
+        assert!(Instruction::block(BlockOp::Scan, Width::Word, Register::rb0(), Register::r1(), Register::r2()).is_err());
+        assert!(Instruction::block(BlockOp::Fill, Width::Byte, Register::rb0(), Register::rb1(), Register::r2()).is_err());
+        assert!(Instruction::block(BlockOp::Fill, Width::Byte, Register::rb0(), Register::r1(), Register::rb2()).is_err());
+        assert!(Instruction::block(BlockOp::Copy, Width::Byte, Register::r1(), Register::r2(), Register::r2()).is_err());
+        assert!(Instruction::block(BlockOp::Copy, Width::Byte, Register::r2(), Register::r1(), Register::r2()).is_err());
+        assert!(Instruction::block(BlockOp::Fill, Width::Byte, Register::rb2(), Register::r1(), Register::r2()).is_err());
+    }
+
+    // Mul
+    alu_test_case!(
+        muli2r(MulOp::Mul),
+        Immediate::byte(0x60), Register::rb0(),
+        Immediate::word(0x600D), Register::r0(),
+        Immediate::word(0x600D), Register::r9(),
+        Immediate::byte(0x60), Register::rb10()
+        ;
+        Immediate::byte(0x60), Register::r0(),
+        Immediate::word(0x600D), Register::rb0(),
+        Immediate::word(0x600D), Register::r10(),
+        Immediate::word(0x600D), Register::rsb()
+    );
+    alu_test_case!(
+        mulip2r(MulOp::Imul),
+        Immediate::word(0x600D), Register::rb0(),
+        Immediate::word(0x600D), Register::r0()
+        ;
+        Immediate::byte(0x60), Register::r0(),
+        Immediate::word(0x600D), Register::r10()
+    );
+    alu_test_case!(
+        mulr2r(MulOp::Div),
+        Register::rb1(), Register::rb0(),
+        Register::r1(), Register::r0()
+        ;
+        Register::rb1(), Register::r0(),
+        Register::r1(), Register::r10(),
+        Register::r1(), Register::rip()
+    );
+    alu_test_case!(
+        mulrp2r(MulOp::Idiv),
+        Register::r1(), Register::rb0(),
+        Register::r1(), Register::r0()
+        ;
+        Register::rb1(), Register::r0(),
+        Register::r1(), Register::r10()
+    );
+
+    // Unary
+    #[test]
+    fn unary() {
+        assert!(Instruction::unaryr(UnaryOp::Inc, Register::rb0()).is_ok());
+        assert!(Instruction::unaryr(UnaryOp::Dec, Register::r0()).is_ok());
+        assert!(Instruction::unaryrp(UnaryOp::Inc, Width::Byte, Register::r0()).is_ok());
+        assert!(Instruction::unaryrp(UnaryOp::Inc, Width::Word, Register::rb0()).is_err());
+        assert!(Instruction::unaryip(UnaryOp::Dec, Width::Word, Immediate::word(0xF337)).is_ok());
+        assert!(Instruction::unaryip(UnaryOp::Dec, Width::Byte, Immediate::byte(0xF3)).is_err());
+    }
+
+    // Operands
+    #[test]
+    fn operands() {
+        use Operand::*;
+        assert_eq!(
+            Instruction::binary(BinaryOp::Mov, Imm(Immediate::word(0x600D)), RegPtr(Register::r0())),
+            Instruction::movi2rp(Immediate::word(0x600D), Register::r0()),
+        );
+        assert_eq!(
+            Instruction::binary(BinaryOp::Alu(AluOp::Cmp), ImmPtr(Immediate::word(0xF337)), Reg(Register::rb1())),
+            Instruction::aluip2r(AluOp::Cmp, Immediate::word(0xF337), Register::rb1()),
+        );
+        assert_eq!(Instruction::unary(UnaryOp::Not, Reg(Register::r3())), Instruction::unaryr(UnaryOp::Not, Register::r3()));
+        assert_eq!(Instruction::jmp(Condition::Zero, RegPtr(Register::r1())), Instruction::jmprp(Condition::Zero, Register::r1()));
+        assert_eq!(Instruction::push(Imm(Immediate::byte(0x60))), Instruction::pushi(Immediate::byte(0x60)));
+
+        assert!(Instruction::binary(BinaryOp::Mov, Reg(Register::r0()), Imm(Immediate::word(0x600D))).is_err());
+        assert!(Instruction::binary(BinaryOp::Xchg, ImmPtr(Immediate::word(0x600D)), RegPtr(Register::r0())).is_err());
+        assert!(Instruction::binary(BinaryOp::Mul(MulOp::Mul), Reg(Register::r1()), RegPtr(Register::r0())).is_err());
+        assert!(Instruction::unary(UnaryOp::Inc, Imm(Immediate::word(0x600D))).is_err());
+        assert!(Instruction::pop(Imm(Immediate::word(0x600D))).is_err());
+
+        // Memory doesn't know the width of the data, so it must be explicit
+        assert!(Instruction::binary(BinaryOp::Mov, ImmPtr(Immediate::word(0x600D)), RegPtr(Register::r0())).is_err());
+        assert!(Instruction::unary(UnaryOp::Inc, RegPtr(Register::r0())).is_err());
+        assert_eq!(
+            Instruction::binary_mem(BinaryOp::Mov, Width::Word, ImmPtr(Immediate::word(0x600D)), RegPtr(Register::r0())),
+            Instruction::movip2rp(Width::Word, Immediate::word(0x600D), Register::r0()),
+        );
+        assert_eq!(
+            Instruction::unary_mem(UnaryOp::Not, Width::Byte, Indexed(Address::new(Register::r0(), None, 0x10))),
+            Instruction::unarym(UnaryOp::Not, Width::Byte, Address::new(Register::r0(), None, 0x10)),
+        );
+        assert!(Instruction::binary_mem(BinaryOp::Mov, Width::Byte, Reg(Register::rb0()), RegPtr(Register::r0())).is_err());
+    }
+
+    // Indexed
+    #[test]
+    fn indexed() {
+        let addr = |base, index, disp| Address::new(base, index, disp);
+        assert!(Instruction::movm2r(addr(Register::r0(), None, 0x10), Register::rb1()).is_ok());
+        assert!(Instruction::movr2m(Register::r1(), addr(Register::r0(), Some((Register::r2(), 8)), 0)).is_ok());
+        assert!(Instruction::movm2r(addr(Register::rb0(), None, 0x10), Register::r1()).is_err());
+        assert!(Instruction::movm2r(addr(Register::r0(), Some((Register::rb2(), 1)), 0x10), Register::r1()).is_err());
+        assert!(Instruction::movr2m(Register::r1(), addr(Register::r0(), Some((Register::r2(), 3)), 0)).is_err());
+        assert!(Instruction::mulm2r(MulOp::Mul, addr(Register::r0(), None, 0), Register::r10()).is_err());
+    }
+
+    // Ports
+    #[test]
+    fn port() {
+        assert!(Instruction::ini(Immediate::byte(0x10), Register::r0()).is_ok());
+        assert!(Instruction::ini(Immediate::word(0x10), Register::r0()).is_err());
+        assert!(Instruction::inr(Register::rb1(), Register::rb0()).is_ok());
+        assert!(Instruction::inr(Register::r1(), Register::rb0()).is_err());
+        assert!(Instruction::outi(Register::rb0(), Immediate::byte(0x10)).is_ok());
+        assert!(Instruction::outr(Register::r0(), Register::r1()).is_err());
+        assert!(Instruction::input(Operand::RegPtr(Register::r1()), Register::r0()).is_err());
+    }
+
+    // Mnemonic
+    #[test]
+    fn mnemonic() {
+        let cases = [
+            ("mov", Mnemonic::Mov),
+            ("movsx", Mnemonic::Movx(Extension::Sign)),
+            ("sbb", Mnemonic::Alu(AluOp::Sbb)),
+            ("jmp", Mnemonic::Jmp(Condition::Always)),
+            ("stosw", Mnemonic::Block(BlockOp::Fill, Width::Word)),
+            ("wfi", Mnemonic::Wfi),
+        ];
+        for (s, mnemonic) in cases {
+            assert_eq!(Mnemonic::from(s), Some(mnemonic));
+            assert_eq!(mnemonic.to_string(), s);
+        }
+        assert_eq!(Mnemonic::from("je"), Some(Mnemonic::Jmp(Condition::Zero)));
+        assert_eq!(Mnemonic::from("movs"), None);
+        assert_eq!(Instruction::movxr2r(Extension::Zero, Register::rb0(), Register::r1()).unwrap().mnemonic(), Mnemonic::Movx(Extension::Zero));
+    }
 }
@@ -0,0 +1,131 @@
+#[allow(unused_imports)]
+use crate::prelude::*;
+
+/// Where an instruction reads its data from or writes it to
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Operand {
+    Imm(Immediate),
+    /// The memory at an immediate address
+    ImmPtr(Immediate),
+    Reg(Register),
+    /// The memory at the address held by a register
+    RegPtr(Register),
+    /// The memory at an address computed from registers and a displacement
+    Indexed(Address),
+    /// The address this far from the next instruction
+    Rel(Immediate),
+}
+
+impl Operand {
+    /// Width of the data it holds, which memory doesn't know by itself
+    pub fn width(&self) -> Option<Width> {
+        match self {
+            Self::Imm(value) => Some(value.width()),
+            Self::Reg(reg) => Some(reg.width()),
+            Self::Rel(_) => Some(Width::Word),
+            Self::ImmPtr(_) | Self::RegPtr(_) | Self::Indexed(_) => None,
+        }
+    }
+
+    pub fn is_memory(&self) -> bool {
+        matches!(self, Self::ImmPtr(_) | Self::RegPtr(_) | Self::Indexed(_))
+    }
+
+    /// Address of a memory operand given the values of the registers, wrapping around.
+    /// RIP already points to the next instruction when the operand is used
+    pub fn effective_address(&self, reg_value : impl Fn(&Register) -> u16) -> Option<u16> {
+        match self {
+            Self::ImmPtr(addr) => Some(addr.get_word(0)),
+            Self::RegPtr(reg) => Some(reg_value(reg)),
+            Self::Indexed(addr) => {
+                let index = addr.index.map_or(0, |(index, scale)| reg_value(&index).wrapping_mul(scale as u16));
+                Some(reg_value(&addr.base).wrapping_add(index).wrapping_add(addr.disp.get_word(0)))
+            },
+            Self::Imm(_) | Self::Reg(_) | Self::Rel(_) => None,
+        }
+    }
+
+    /// Registers used to compute the address of a memory operand
+    pub fn address_registers(&self) -> Vec<Register> {
+        match self {
+            Self::RegPtr(reg) => vec![*reg],
+            Self::Indexed(addr) => core::iter::once(addr.base).chain(addr.index.map(|(index, _)| index)).collect(),
+            Self::Imm(_) | Self::ImmPtr(_) | Self::Reg(_) | Self::Rel(_) => vec![],
+        }
+    }
+
+    /// Registers read to get the value of the operand, RIP for relative ones
+    pub fn read_registers(&self) -> Vec<Register> {
+        match self {
+            Self::Reg(reg) => vec![*reg],
+            Self::Rel(_) => vec![Register::rip()],
+            _ => self.address_registers(),
+        }
+    }
+}
+
+/// How sasm writes it. Relative operands only come from labels in position independent code,
+/// so they are written like the expression they stand for
+impl core::fmt::Display for Operand {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::Imm(value) => write!(f, "{value}"),
+            Self::ImmPtr(addr) => write!(f, "[{addr}]"),
+            Self::Reg(reg) => write!(f, "{reg}"),
+            Self::RegPtr(reg) => write!(f, "[{reg}]"),
+            Self::Indexed(addr) => write!(f, "[{addr}]"),
+            Self::Rel(disp) => write!(f, "({} + {disp})", Register::rip()),
+        }
+    }
+}
+
+/// `base + index * scale + disp`, where the index is optional
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Address {
+    pub base : Register,
+    pub index : Option<(Register, u8)>,
+    pub disp : Immediate,
+}
+
+impl Address {
+    pub const SCALES : [u8; 4] = [1, 2, 4, 8];
+
+    pub fn new(base : Register, index : Option<(Register, u8)>, disp : u16) -> Self {
+        Self { base, index, disp : Immediate::word(disp) }
+    }
+
+    /// `[rip + disp]`, RIP already points to the next instruction when it is used
+    pub fn rip_relative(disp : u16) -> Self {
+        Self::new(Register::rip(), None, disp)
+    }
+
+    /// Both registers must be word registers and the scale one of `SCALES`
+    pub fn is_valid(&self) -> bool {
+        self.base.width() == Width::Word
+        && self.disp.width() == Width::Word
+        && self.index.is_none_or(|(index, scale)| index.width() == Width::Word && Self::SCALES.contains(&scale))
+    }
+
+    /// Bit 2 says whether there is an index, the low two bits are the log2 of its scale
+    pub(super) fn mode(&self) -> u8 {
+        self.index.map_or(0, |(_, scale)| 0x04 | scale.trailing_zeros() as u8)
+    }
+
+    pub(super) fn from_mode(mode : u8, regs : u8, disp : Immediate) -> Self {
+        let index = (mode & 0x04 != 0).then(|| (Register::from_dest(Width::Word, regs), 1 << (mode & 0x03)));
+        Self { base : Register::from_src(Width::Word, regs), index, disp }
+    }
+}
+
+/// The displacement is always written, so it can't be mistaken for a plain register pointer
+impl core::fmt::Display for Address {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{}", self.base)?;
+        match self.index {
+            Some((index, 1)) => write!(f, " + {index}")?,
+            Some((index, scale)) => write!(f, " + {index}*{scale}")?,
+            None => (),
+        }
+        write!(f, " + {}", self.disp)
+    }
+}
@@ -0,0 +1,213 @@
+#[allow(unused_imports)]
+use crate::prelude::*;
+
+/// Declares an operation enum from its table of codes and mnemonics, the first mnemonic being the canonical one
+macro_rules! ops {
+    (
+        $(#[$meta:meta])*
+        pub enum $Op:ident {
+            $( $(#[$variant_meta:meta])* $Variant:ident = $code:literal => $mnemonic:literal $(| $alias:literal)* ),+ $(,)?
+        }
+    ) => {
+        $(#[$meta])*
+        #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+        pub enum $Op {
+            $( $(#[$variant_meta])* $Variant, )+
+        }
+
+        impl $Op {
+            pub fn from(s : &str) -> Option<Self> {
+                match &*s.to_lowercase() {
+                    $( $mnemonic $(| $alias)* => Some(Self::$Variant), )+
+                    _ => None,
+                }
+            }
+
+            pub fn from_code(code : u8) -> Option<Self> {
+                match code {
+                    $( $code => Some(Self::$Variant), )+
+                    _ => None,
+                }
+            }
+
+            pub fn code(&self) -> u8 {
+                match self {
+                    $( Self::$Variant => $code, )+
+                }
+            }
+
+            pub fn mnemonic(&self) -> &'static str {
+                match self {
+                    $( Self::$Variant => $mnemonic, )+
+                }
+            }
+        }
+    };
+}
+
+ops! {
+    /// How a byte is widened into a word, named after the move that uses it.
+    pub enum Extension {
+        Zero = 0x00 => "movzx",
+        Sign = 0x01 => "movsx",
+    }
+}
+
+impl Extension {
+    pub fn extend(&self, value : u8) -> u16 {
+        match self {
+            Self::Zero => value as u16,
+            Self::Sign => value as i8 as i16 as u16,
+        }
+    }
+}
+
+ops! {
+    /// Operations that combine a source with a destination and store the result in the destination.
+    pub enum AluOp {
+        Add = 0x00 => "add",
+        Sub = 0x01 => "sub",
+        Adc = 0x02 => "adc",
+        Sbb = 0x03 => "sbb",
+        And = 0x04 => "and",
+        Or = 0x05 => "or",
+        Xor = 0x06 => "xor",
+        Shl = 0x07 => "shl",
+        Shr = 0x08 => "shr",
+        Sar = 0x09 => "sar",
+        Rol = 0x0A => "rol",
+        Ror = 0x0B => "ror",
+        Cmp = 0x0C => "cmp",
+        Test = 0x0D => "test",
+    }
+}
+
+impl AluOp {
+    /// Whether the result is written back to the destination, or only the flags are updated
+    pub fn stores_result(&self) -> bool {
+        !matches!(self, Self::Cmp | Self::Test)
+    }
+}
+
+/// Operations that read a source and write or update a destination.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BinaryOp {
+    Mov,
+    /// Widens a byte into a word register
+    Movx(Extension),
+    Xchg,
+    /// Compares `r0` with the destination, stores the source into it if equal or loads it into `r0` otherwise
+    Cmpxchg,
+    Alu(AluOp),
+    Mul(MulOp),
+}
+
+impl BinaryOp {
+    pub fn from(s : &str) -> Option<Self> {
+        match &*s.to_lowercase() {
+            "mov" => Some(Self::Mov),
+            "xchg" => Some(Self::Xchg),
+            "cmpxchg" => Some(Self::Cmpxchg),
+            s => Extension::from(s).map(Self::Movx)
+                .or_else(|| AluOp::from(s).map(Self::Alu))
+                .or_else(|| MulOp::from(s).map(Self::Mul)),
+        }
+    }
+
+    pub fn mnemonic(&self) -> &'static str {
+        match self {
+            Self::Mov => "mov",
+            Self::Movx(ext) => ext.mnemonic(),
+            Self::Xchg => "xchg",
+            Self::Cmpxchg => "cmpxchg",
+            Self::Alu(op) => op.mnemonic(),
+            Self::Mul(op) => op.mnemonic(),
+        }
+    }
+}
+
+ops! {
+    /// Operations that modify their only operand in place.
+    pub enum UnaryOp {
+        Inc = 0x00 => "inc",
+        Dec = 0x01 => "dec",
+        Not = 0x02 => "not",
+    }
+}
+
+ops! {
+    /// Multiplications and divisions, whose results are twice as wide as their operands.
+    pub enum MulOp {
+        Mul = 0x00 => "mul",
+        Imul = 0x01 => "imul",
+        Div = 0x02 => "div",
+        Idiv = 0x03 => "idiv",
+    }
+}
+
+ops! {
+    /// String operations over blocks of memory, repeated as many times as the count register says.
+    /// Their mnemonics take the width of the elements as a suffix.
+    pub enum BlockOp {
+        /// Copies the block at the source into the destination
+        Copy = 0x00 => "movs",
+        /// Stores the value into the destination
+        Fill = 0x01 => "stos",
+        /// Compares the source and the destination until they differ
+        Compare = 0x02 => "cmps",
+        /// Compares the destination against the value until they match
+        Scan = 0x03 => "scas",
+    }
+}
+
+impl BlockOp {
+    /// Whether the first operand is a pointer to a source block, or a value
+    pub fn has_src(&self) -> bool {
+        matches!(self, Self::Copy | Self::Compare)
+    }
+}
+
+ops! {
+    /// Condition under which a jump is taken, evaluated over the `Flags` register.
+    pub enum Condition {
+        Always = 0x00 => "jmp",
+        Zero = 0x01 => "jz" | "je",
+        NotZero = 0x02 => "jnz" | "jne",
+        Carry = 0x03 => "jc" | "jb",
+        NotCarry = 0x04 => "jnc" | "jae",
+        Sign = 0x05 => "js",
+        NotSign = 0x06 => "jns",
+        Overflow = 0x07 => "jo",
+        NotOverflow = 0x08 => "jno",
+        Above = 0x09 => "ja",
+        BelowEqual = 0x0A => "jbe",
+        Greater = 0x0B => "jg",
+        GreaterEqual = 0x0C => "jge",
+        Less = 0x0D => "jl",
+        LessEqual = 0x0E => "jle",
+    }
+}
+
+impl Condition {
+    /// Above/Below compare unsigned values while Greater/Less compare signed ones, both after a `cmp`
+    pub fn holds(&self, flags : Flags) -> bool {
+        use Condition::*;
+        match self {
+            Always => true,
+            Zero => flags.zero(),
+            NotZero => !flags.zero(),
+            Carry => flags.carry(),
+            NotCarry => !flags.carry(),
+            Sign => flags.sign(),
+            NotSign => !flags.sign(),
+            Overflow => flags.overflow(),
+            NotOverflow => !flags.overflow(),
+            Above => !flags.carry() && !flags.zero(),
+            BelowEqual => flags.carry() || flags.zero(),
+            Greater => !flags.zero() && flags.sign() == flags.overflow(),
+            GreaterEqual => flags.sign() == flags.overflow(),
+            Less => flags.sign() != flags.overflow(),
+            LessEqual => flags.zero() || flags.sign() != flags.overflow(),
+        }
+    }
+}
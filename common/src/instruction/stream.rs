@@ -0,0 +1,134 @@
+#[allow(unused_imports)]
+use crate::prelude::*;
+use std::io::{ErrorKind, Read};
+
+/// What was found at some offset of a stream of bytes
+#[derive(Debug, Clone, PartialEq)]
+pub enum Decoded {
+    Instruction(Instruction),
+    /// A byte that doesn't start a valid instruction, like embedded data or a truncated instruction
+    Data,
+}
+
+/// Decodes instructions one after the other from a slice or any other `Read`, yielding the offset,
+/// what was decoded and its raw bytes. Undecodable bytes are yielded as data one at a time,
+/// decoding starts again from the next one
+pub struct InstructionStream<R> {
+    reader : R,
+    buffer : Vec<u8>,
+    offset : usize,
+    eof : bool,
+}
+
+impl<R : Read> InstructionStream<R> {
+    pub fn new(reader : R) -> Self {
+        Self { reader, buffer : Vec::with_capacity(Instruction::MAX_LEN as usize), offset : 0, eof : false }
+    }
+
+    /// Reads until there are enough bytes for the longest instruction, or the reader runs out
+    fn fill(&mut self) -> Result<()> {
+        let mut chunk = [0; Instruction::MAX_LEN as usize];
+        while !self.eof && self.buffer.len() < chunk.len() {
+            let missing = chunk.len() - self.buffer.len();
+            match self.reader.read(&mut chunk[..missing]) {
+                Ok(0) => self.eof = true,
+                Ok(read) => self.buffer.extend_from_slice(&chunk[..read]),
+                Err(err) if err.kind() == ErrorKind::Interrupted => (),
+                Err(err) => return Err(Error::Misc(err.to_string())),
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<R : Read> Iterator for InstructionStream<R> {
+    type Item = Result<(usize, Decoded, Vec<u8>)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Err(err) = self.fill() {
+            return Some(Err(err));
+        }
+        if self.buffer.is_empty() {
+            return None;
+        }
+
+        // Truncated instructions don't have all the bytes their opcode says
+        let instr = Instruction::len_of(self.buffer[0])
+            .and_then(|len| self.buffer.get(..len as usize))
+            .and_then(|bytes| Instruction::decompile(bytes).ok());
+        let (decoded, len) = match instr {
+            Some(instr) => {
+                let len = instr.len() as usize;
+                (Decoded::Instruction(instr), len)
+            },
+            None => (Decoded::Data, 1),
+        };
+        let offset = self.offset;
+        self.offset += len;
+        Some(Ok((offset, decoded, self.buffer.drain(..len).collect())))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Hands out a single byte per read
+    struct Trickle<'a>(&'a [u8]);
+
+    impl Read for Trickle<'_> {
+        fn read(&mut self, buf : &mut [u8]) -> std::io::Result<usize> {
+            match (self.0.split_first(), buf.first_mut()) {
+                (Some((byte, rest)), Some(slot)) => {
+                    *slot = *byte;
+                    self.0 = rest;
+                    Ok(1)
+                },
+                _ => Ok(0),
+            }
+        }
+    }
+
+    fn rom() -> (Vec<Instruction>, Vec<u8>) {
+        let instructions = vec![
+            Instruction::movi2r(Immediate::word(0x600D), Register::r0()).unwrap(),
+            Instruction::unarym(UnaryOp::Inc, Width::Word, Address::new(Register::r0(), Some((Register::r1(), 2)), 0x10)).unwrap(),
+            Instruction::hlt().unwrap(),
+        ];
+        let mut bytes = instructions[0].compile();
+        bytes.push(0xFF); // Data between instructions
+        bytes.extend(instructions[1].compile());
+        bytes.extend(instructions[2].compile());
+        bytes.extend(&instructions[0].compile()[..2]); // Truncated at the end
+        (instructions, bytes)
+    }
+
+    fn check(decoded : Vec<(usize, Decoded, Vec<u8>)>, instructions : &[Instruction], bytes : &[u8]) {
+        let expected = [
+            (0, Decoded::Instruction(instructions[0].clone())),
+            (4, Decoded::Data),
+            (5, Decoded::Instruction(instructions[1].clone())),
+            (12, Decoded::Instruction(instructions[2].clone())),
+            (14, Decoded::Data),
+            (15, Decoded::Data),
+        ];
+        assert_eq!(decoded.iter().map(|(offset, decoded, _)| (*offset, decoded.clone())).collect::<Vec<_>>(), expected);
+        for (offset, _, raw) in decoded {
+            assert_eq!(raw, bytes[offset..offset + raw.len()]);
+        }
+    }
+
+    #[test]
+    fn slice() {
+        let (instructions, bytes) = rom();
+        let decoded = InstructionStream::new(bytes.as_slice()).collect::<Result<Vec<_>>>().unwrap();
+        check(decoded, &instructions, &bytes);
+    }
+
+    #[test]
+    fn reader() {
+        let (instructions, bytes) = rom();
+        let decoded = InstructionStream::new(Trickle(&bytes)).collect::<Result<Vec<_>>>().unwrap();
+        check(decoded, &instructions, &bytes);
+    }
+}
@@ -0,0 +1,468 @@
+#[allow(unused_imports)]
+use crate::prelude::*;
+use super::*;
+use super::BinaryOp::*;
+use super::Operand::*;
+use super::compile::Encoder;
+use super::decompile::Decoder;
+
+/// How sasm spells an instruction, regardless of its operands
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Mnemonic {
+    Nop,
+    Mov,
+    Movx(Extension),
+    Xchg,
+    Cmpxchg,
+    Alu(AluOp),
+    Unary(UnaryOp),
+    Jmp(Condition),
+    Push,
+    Pop,
+    Call,
+    Ret,
+    Int,
+    Iret,
+    Sti,
+    Cli,
+    Hlt,
+    Wfi,
+    Mul(MulOp),
+    Block(BlockOp, Width),
+    In,
+    Out,
+}
+
+impl Mnemonic {
+    pub fn from(s : &str) -> Option<Self> {
+        use Mnemonic::*;
+        let s = s.to_lowercase();
+        let plain = match &*s {
+            "nop" => Some(Nop),
+            "push" => Some(Push),
+            "pop" => Some(Pop),
+            "call" => Some(Call),
+            "ret" => Some(Ret),
+            "int" => Some(Int),
+            "iret" => Some(Iret),
+            "sti" => Some(Sti),
+            "cli" => Some(Cli),
+            "hlt" => Some(Hlt),
+            "wfi" => Some(Wfi),
+            "in" => Some(In),
+            "out" => Some(Out),
+            _ => None,
+        };
+
+        plain
+            .or_else(|| BinaryOp::from(&s).map(Self::binary))
+            .or_else(|| UnaryOp::from(&s).map(Unary))
+            .or_else(|| Condition::from(&s).map(Jmp))
+            .or_else(|| Self::from_block(&s))
+    }
+
+    /// Block mnemonics end with the width of the elements
+    fn from_block(s : &str) -> Option<Self> {
+        let width = match s.chars().last()? {
+            'b' => Width::Byte,
+            'w' => Width::Word,
+            _ => return None,
+        };
+        BlockOp::from(&s[..s.len() - 1]).map(|op| Self::Block(op, width))
+    }
+
+    /// Number of operands it takes
+    pub fn arity(&self) -> usize {
+        use Mnemonic::*;
+        match self {
+            Nop | Ret | Iret | Sti | Cli | Hlt | Wfi => 0,
+            Unary(_) | Jmp(_) | Push | Pop | Call | Int => 1,
+            Mov | Movx(_) | Xchg | Cmpxchg | Alu(_) | Mul(_) | In | Out => 2,
+            Block(_, _) => 3,
+        }
+    }
+
+    pub fn binary(op : BinaryOp) -> Self {
+        match op {
+            Mov => Self::Mov,
+            Movx(ext) => Self::Movx(ext),
+            Xchg => Self::Xchg,
+            Cmpxchg => Self::Cmpxchg,
+            Alu(op) => Self::Alu(op),
+            Mul(op) => Self::Mul(op),
+        }
+    }
+
+    /// The operation of a two operand mnemonic
+    pub fn binary_op(&self) -> Option<BinaryOp> {
+        match *self {
+            Self::Mov => Some(Mov),
+            Self::Movx(ext) => Some(Movx(ext)),
+            Self::Xchg => Some(Xchg),
+            Self::Cmpxchg => Some(Cmpxchg),
+            Self::Alu(op) => Some(Alu(op)),
+            Self::Mul(op) => Some(Mul(op)),
+            _ => None,
+        }
+    }
+}
+
+impl core::fmt::Display for Mnemonic {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        use Mnemonic::*;
+        match self {
+            Nop => write!(f, "nop"),
+            Mov | Movx(_) | Xchg | Cmpxchg | Alu(_) | Mul(_) => write!(f, "{}", self.binary_op().unwrap().mnemonic()),
+            Unary(op) => write!(f, "{}", op.mnemonic()),
+            Jmp(cond) => write!(f, "{}", cond.mnemonic()),
+            Push => write!(f, "push"),
+            Pop => write!(f, "pop"),
+            Call => write!(f, "call"),
+            Ret => write!(f, "ret"),
+            Int => write!(f, "int"),
+            Iret => write!(f, "iret"),
+            Sti => write!(f, "sti"),
+            Cli => write!(f, "cli"),
+            Hlt => write!(f, "hlt"),
+            Wfi => write!(f, "wfi"),
+            In => write!(f, "in"),
+            Out => write!(f, "out"),
+            Block(op, Width::Byte) => write!(f, "{}b", op.mnemonic()),
+            Block(op, Width::Word) => write!(f, "{}w", op.mnemonic()),
+        }
+    }
+}
+
+/// Generates everything that depends on the encoding of an `Instruction` from one row per opcode:
+///
+/// `constructor Pattern => opcode [sized(width)] [param : Type = Kind[(width)], ...] [if extra validation];`
+///
+/// The pattern picks which operands the opcode takes and binds the params, the constructor
+/// takes the params in the order they are listed and builds the instruction back from the pattern.
+/// An instruction that matches no row has no encoding, so it is invalid.
+///
+/// Sized instructions take two consecutive opcodes, for byte and word data, and `width` says which one applies.
+/// Each param is encoded as its Kind says, in this order after the opcode:
+/// - `Op`: a code byte, e.g. the `AluOp`
+/// - `Src` and `Dest`: the low and high nibbles of the registers byte, which is always present
+/// - `Extra`: a register in its own byte
+/// - `Imm`: two little-endian bytes, even for byte immediates
+/// - `Addr`: the registers of an `Address` and its mode in two extra bytes, its displacement as an immediate
+/// - `Width`: nothing, it is the width of a sized opcode
+///
+/// The width in parentheses is the width the param must have, it may use the data width
+/// named in the header and the params that come before.
+macro_rules! instructions {
+    (
+        width = $w:ident;
+
+        $(
+            $ctor:ident $Variant:ident $(( $($pattern:tt)* ))? => $opcode:literal $(sized($sized:expr))?
+                [ $($param:ident : $Type:ty = $kind:ident $(($width:expr))?),* $(,)? ]
+                $(if $valid:expr)?
+        );+ $(;)?
+    ) => {
+        impl Instruction {
+            /// Length in bytes of the longest encoded instruction
+            pub const MAX_LEN : u16 = {
+                let lens = [$( 2 $(+ instructions!(@len $kind))* ),+];
+                let mut max = 0;
+                let mut i = 0;
+                while i < lens.len() {
+                    if lens[i] > max { max = lens[i]; }
+                    i += 1;
+                }
+                max
+            };
+
+            $(
+                pub fn $ctor($($param : $Type),*) -> Result<Self> {
+                    Self::$Variant $(( $($pattern)* ))?.check_valid()
+                }
+            )+
+
+            #[allow(unused_variables)]
+            pub(super) fn is_valid(&self) -> bool {
+                match self {
+                    $(
+                        Self::$Variant $(( $($pattern)* ))? => {
+                            let $w = instructions!(@width $($sized)?);
+                            true $(&& instructions!(@valid $kind, $param $(, $width)?))* $(&& $valid)?
+                        }
+                    )+
+                    _ => false,
+                }
+            }
+
+            #[allow(unused_variables)]
+            pub fn opcode(&self) -> u8 {
+                match self {
+                    $(
+                        Self::$Variant $(( $($pattern)* ))? => $opcode $(+ match $sized {
+                            Width::Byte => 0,
+                            Width::Word => 1,
+                        })?,
+                    )+
+                    _ => unreachable!("{self:?} has no encoding"),
+                }
+            }
+
+            #[allow(unused_variables, clippy::len_without_is_empty)]
+            pub fn len(&self) -> u16 {
+                match self {
+                    $( Self::$Variant $(( $($pattern)* ))? => 2 $(+ instructions!(@len $kind))*, )+
+                    _ => unreachable!("{self:?} has no encoding"),
+                }
+            }
+
+            /// Length of the instructions that start with `opcode`, without decoding the rest
+            pub fn len_of(opcode : u8) -> Option<u16> {
+                $(
+                    if instructions!(@match opcode, $opcode $(, $sized)?).is_some() {
+                        return Some(2 $(+ instructions!(@len $kind))*);
+                    }
+                )+
+                None
+            }
+
+            /// Encodes into the start of `bytes` and returns how many it took, without allocating
+            #[allow(unused_variables, unused_mut)]
+            pub fn encode(&self, bytes : &mut [u8]) -> Result<usize> {
+                let len = self.len() as usize;
+                let available = bytes.len();
+                let bytes = bytes.get_mut(..len).ok_or(Error::BufferTooSmall(available, len))?;
+                match self {
+                    $(
+                        Self::$Variant $(( $($pattern)* ))? => {
+                            let mut encoder = Encoder::new(
+                                bytes,
+                                self.opcode(),
+                                0 $(+ instructions!(@ops $kind))*,
+                                0 $(+ instructions!(@extras $kind))*,
+                            );
+                            $( instructions!(@encode $kind, $param, encoder); )*
+                        }
+                    )+
+                    _ => unreachable!("{self:?} has no encoding"),
+                }
+                Ok(len)
+            }
+
+            #[allow(unused_variables, unused_mut)]
+            pub fn decompile(bytes : &[u8]) -> Result<Self> {
+                let opcode = *bytes.first().ok_or(Error::NoOpcode)?;
+                $(
+                    if let Some($w) = instructions!(@match opcode, $opcode $(, $sized)?) {
+                        let mut decoder = Decoder::new(
+                            bytes,
+                            0 $(+ instructions!(@ops $kind))*,
+                            0 $(+ instructions!(@extras $kind))*,
+                        );
+                        $( let $param = instructions!(@decode $kind, $Type, decoder, $w $(, $width)?); )*
+                        return Self::$ctor($($param),*);
+                    }
+                )+
+                Err(Error::NoSuchOpcode(opcode))
+            }
+
+            /// Number of immediate params
+            #[allow(unused_variables)]
+            pub(super) fn imm_count(&self) -> usize {
+                match self {
+                    $( Self::$Variant $(( $($pattern)* ))? => 0 $(+ instructions!(@imms $kind))*, )+
+                    _ => 0,
+                }
+            }
+
+            /// Replaces the value of the `idx`th immediate param, keeping its width
+            #[allow(unused_variables, unused_mut)]
+            pub(super) fn replace_nth_imm(self, idx : usize, value : u16) -> Result<Self> {
+                match self {
+                    $(
+                        Self::$Variant $(( $($pattern)* ))? => {
+                            let mut nth = 0;
+                            $( let $param = instructions!(@replace $kind, $param, nth, idx, value); )*
+                            Self::$ctor($($param),*)
+                        }
+                    )+
+                    _ => Err(Error::InvalidOperands(self)),
+                }
+            }
+        }
+    };
+
+    // Unsized instructions have no data width, byte is as good as any
+    (@width) => { Width::Byte };
+    (@width $sized:expr) => { $sized };
+
+    (@match $opcode:ident, $base:literal) => { ($opcode == $base).then_some(Width::Byte) };
+    (@match $opcode:ident, $base:literal, $sized:expr) => {
+        match $opcode.wrapping_sub($base) {
+            0 => Some(Width::Byte),
+            1 => Some(Width::Word),
+            _ => None,
+        }
+    };
+
+    (@valid Op, $param:ident) => { true };
+    (@valid Width, $param:ident) => { true };
+    (@valid Addr, $param:ident) => { $param.is_valid() };
+    (@valid $kind:ident, $param:ident, $width:expr) => { $param.width() == ($width) };
+
+    (@len Op) => { 1 };
+    (@len Extra) => { 1 };
+    (@len Imm) => { 2 };
+    (@len Addr) => { 4 };
+    (@len $kind:ident) => { 0 };
+
+    (@ops Op) => { 1 };
+    (@ops $kind:ident) => { 0 };
+
+    (@extras Extra) => { 1 };
+    (@extras Addr) => { 2 };
+    (@extras $kind:ident) => { 0 };
+
+    (@imms Imm) => { 1 };
+    (@imms Addr) => { 1 };
+    (@imms $kind:ident) => { 0 };
+
+    (@encode Op, $param:ident, $encoder:ident) => { $encoder.op($param.code()) };
+    (@encode Width, $param:ident, $encoder:ident) => { () };
+    (@encode Src, $param:ident, $encoder:ident) => { $encoder.src($param) };
+    (@encode Dest, $param:ident, $encoder:ident) => { $encoder.dest($param) };
+    (@encode Extra, $param:ident, $encoder:ident) => { $encoder.extra($param) };
+    (@encode Imm, $param:ident, $encoder:ident) => { $encoder.imm($param) };
+    (@encode Addr, $param:ident, $encoder:ident) => { $encoder.addr($param) };
+
+    (@decode Op, $Type:ty, $decoder:ident, $w:ident) => { $decoder.op(<$Type>::from_code)? };
+    (@decode Width, $Type:ty, $decoder:ident, $w:ident) => { $w };
+    (@decode Addr, $Type:ty, $decoder:ident, $w:ident) => { $decoder.addr()? };
+    (@decode Src, $Type:ty, $decoder:ident, $w:ident, $width:expr) => { $decoder.src($width)? };
+    (@decode Dest, $Type:ty, $decoder:ident, $w:ident, $width:expr) => { $decoder.dest($width)? };
+    (@decode Extra, $Type:ty, $decoder:ident, $w:ident, $width:expr) => { $decoder.extra($width)? };
+    (@decode Imm, $Type:ty, $decoder:ident, $w:ident, $width:expr) => { $decoder.imm($width)? };
+
+    (@replace Imm, $param:ident, $nth:ident, $idx:ident, $value:ident) => {{
+        $nth += 1;
+        if $nth - 1 == $idx { Immediate::new_unchecked($param.width(), $value) } else { $param }
+    }};
+    (@replace Addr, $param:ident, $nth:ident, $idx:ident, $value:ident) => {{
+        $nth += 1;
+        if $nth - 1 == $idx { Address { disp : Immediate::word($value), ..$param } } else { $param }
+    }};
+    (@replace $kind:ident, $param:ident, $nth:ident, $idx:ident, $value:ident) => { $param };
+}
+
+instructions! {
+    width = w;
+
+    nop Nop => 0x00 [];
+
+    movi2r Binary(Mov, Imm(src), Reg(dest)) => 0x01 sized(src.width()) [src : Immediate = Imm(w), dest : Register = Dest(w)];
+    movi2rp Binary(Mov, Imm(src), RegPtr(dest)) => 0x03 sized(src.width()) [src : Immediate = Imm(w), dest : Register = Dest(Width::Word)];
+    movi2ip Binary(Mov, Imm(src), ImmPtr(dest)) => 0x05 sized(src.width()) [src : Immediate = Imm(w), dest : Immediate = Imm(Width::Word)];
+    movip2r Binary(Mov, ImmPtr(src), Reg(dest)) => 0x07 sized(dest.width()) [src : Immediate = Imm(Width::Word), dest : Register = Dest(w)];
+    movr2r Binary(Mov, Reg(src), Reg(dest)) => 0x0B sized(src.width()) [src : Register = Src(w), dest : Register = Dest(w)];
+    movr2rp Binary(Mov, Reg(src), RegPtr(dest)) => 0x0D sized(src.width()) [src : Register = Src(w), dest : Register = Dest(Width::Word)];
+    movr2ip Binary(Mov, Reg(src), ImmPtr(dest)) => 0x0F sized(src.width()) [src : Register = Src(w), dest : Immediate = Imm(Width::Word)];
+    movrp2r Binary(Mov, RegPtr(src), Reg(dest)) => 0x11 sized(dest.width()) [src : Register = Src(Width::Word), dest : Register = Dest(w)];
+
+    // Widen a byte into a word register
+    movxip2r Binary(Movx(ext), ImmPtr(src), Reg(dest)) => 0x15 [ext : Extension = Op, src : Immediate = Imm(Width::Word), dest : Register = Dest(Width::Word)];
+    movxr2r Binary(Movx(ext), Reg(src), Reg(dest)) => 0x16 [ext : Extension = Op, src : Register = Src(Width::Byte), dest : Register = Dest(Width::Word)];
+    movxrp2r Binary(Movx(ext), RegPtr(src), Reg(dest)) => 0x17 [ext : Extension = Op, src : Register = Src(Width::Word), dest : Register = Dest(Width::Word)];
+
+    // Both operands are in memory, so the width of the data is explicit
+    movip2rp BinaryMem(Mov, width, ImmPtr(src), RegPtr(dest)) => 0x18 sized(*width) [width : Width = Width, src : Immediate = Imm(Width::Word), dest : Register = Dest(Width::Word)];
+    movip2ip BinaryMem(Mov, width, ImmPtr(src), ImmPtr(dest)) => 0x1A sized(*width) [width : Width = Width, src : Immediate = Imm(Width::Word), dest : Immediate = Imm(Width::Word)];
+    movrp2rp BinaryMem(Mov, width, RegPtr(src), RegPtr(dest)) => 0x1C sized(*width) [width : Width = Width, src : Register = Src(Width::Word), dest : Register = Dest(Width::Word)];
+    movrp2ip BinaryMem(Mov, width, RegPtr(src), ImmPtr(dest)) => 0x1E sized(*width) [width : Width = Width, src : Register = Src(Width::Word), dest : Immediate = Imm(Width::Word)];
+
+    // Mirrors the Mov opcodes
+    alui2r Binary(Alu(op), Imm(src), Reg(dest)) => 0x21 sized(src.width()) [op : AluOp = Op, src : Immediate = Imm(w), dest : Register = Dest(w)];
+    alui2rp Binary(Alu(op), Imm(src), RegPtr(dest)) => 0x23 sized(src.width()) [op : AluOp = Op, src : Immediate = Imm(w), dest : Register = Dest(Width::Word)];
+    alui2ip Binary(Alu(op), Imm(src), ImmPtr(dest)) => 0x25 sized(src.width()) [op : AluOp = Op, src : Immediate = Imm(w), dest : Immediate = Imm(Width::Word)];
+    aluip2r Binary(Alu(op), ImmPtr(src), Reg(dest)) => 0x27 sized(dest.width()) [op : AluOp = Op, src : Immediate = Imm(Width::Word), dest : Register = Dest(w)];
+    alur2r Binary(Alu(op), Reg(src), Reg(dest)) => 0x2B sized(src.width()) [op : AluOp = Op, src : Register = Src(w), dest : Register = Dest(w)];
+    alur2rp Binary(Alu(op), Reg(src), RegPtr(dest)) => 0x2D sized(src.width()) [op : AluOp = Op, src : Register = Src(w), dest : Register = Dest(Width::Word)];
+    alur2ip Binary(Alu(op), Reg(src), ImmPtr(dest)) => 0x2F sized(src.width()) [op : AluOp = Op, src : Register = Src(w), dest : Immediate = Imm(Width::Word)];
+    alurp2r Binary(Alu(op), RegPtr(src), Reg(dest)) => 0x31 sized(dest.width()) [op : AluOp = Op, src : Register = Src(Width::Word), dest : Register = Dest(w)];
+    aluip2rp BinaryMem(Alu(op), width, ImmPtr(src), RegPtr(dest)) => 0x38 sized(*width) [op : AluOp = Op, width : Width = Width, src : Immediate = Imm(Width::Word), dest : Register = Dest(Width::Word)];
+    aluip2ip BinaryMem(Alu(op), width, ImmPtr(src), ImmPtr(dest)) => 0x3A sized(*width) [op : AluOp = Op, width : Width = Width, src : Immediate = Imm(Width::Word), dest : Immediate = Imm(Width::Word)];
+    alurp2rp BinaryMem(Alu(op), width, RegPtr(src), RegPtr(dest)) => 0x3C sized(*width) [op : AluOp = Op, width : Width = Width, src : Register = Src(Width::Word), dest : Register = Dest(Width::Word)];
+    alurp2ip BinaryMem(Alu(op), width, RegPtr(src), ImmPtr(dest)) => 0x3E sized(*width) [op : AluOp = Op, width : Width = Width, src : Register = Src(Width::Word), dest : Immediate = Imm(Width::Word)];
+
+    unaryr Unary(op, Reg(dest)) => 0x40 sized(dest.width()) [op : UnaryOp = Op, dest : Register = Dest(w)];
+    unaryrp UnaryMem(op, width, RegPtr(dest)) => 0x44 sized(*width) [op : UnaryOp = Op, width : Width = Width, dest : Register = Dest(Width::Word)];
+    unaryip UnaryMem(op, width, ImmPtr(dest)) => 0x46 sized(*width) [op : UnaryOp = Op, width : Width = Width, dest : Immediate = Imm(Width::Word)];
+    unarym UnaryMem(op, width, Indexed(dest)) => 0x48 sized(*width) [op : UnaryOp = Op, width : Width = Width, dest : Address = Addr];
+
+    jmpi Jmp(cond, Imm(target)) => 0x50 [cond : Condition = Op, target : Immediate = Imm(Width::Word)];
+    jmpip Jmp(cond, ImmPtr(target)) => 0x51 [cond : Condition = Op, target : Immediate = Imm(Width::Word)];
+    jmpr Jmp(cond, Reg(target)) => 0x52 [cond : Condition = Op, target : Register = Src(Width::Word)];
+    jmprp Jmp(cond, RegPtr(target)) => 0x53 [cond : Condition = Op, target : Register = Src(Width::Word)];
+
+    calli Call(Imm(target)) => 0x54 [target : Immediate = Imm(Width::Word)];
+    callip Call(ImmPtr(target)) => 0x55 [target : Immediate = Imm(Width::Word)];
+    callr Call(Reg(target)) => 0x56 [target : Register = Src(Width::Word)];
+    callrp Call(RegPtr(target)) => 0x57 [target : Register = Src(Width::Word)];
+    ret Ret => 0x58 [];
+
+    int Int(vector) => 0x59 [vector : Immediate = Imm(Width::Byte)];
+    iret Iret => 0x5A [];
+    sti Sti => 0x5B [];
+    cli Cli => 0x5C [];
+    hlt Hlt => 0x5D [];
+    wfi Wfi => 0x5E [];
+
+    pushi Push(Imm(value)) => 0x60 sized(value.width()) [value : Immediate = Imm(w)];
+    pushr Push(Reg(src)) => 0x62 sized(src.width()) [src : Register = Src(w)];
+    popr Pop(Reg(dest)) => 0x64 sized(dest.width()) [dest : Register = Dest(w)];
+
+    // Ports are a separate byte address space
+    ini In(Imm(port), dest) => 0x66 sized(dest.width()) [port : Immediate = Imm(Width::Byte), dest : Register = Dest(w)];
+    inr In(Reg(port), dest) => 0x68 sized(dest.width()) [port : Register = Src(Width::Byte), dest : Register = Dest(w)];
+    outi Out(src, Imm(port)) => 0x6A sized(src.width()) [src : Register = Src(w), port : Immediate = Imm(Width::Byte)];
+    outr Out(src, Reg(port)) => 0x6C sized(src.width()) [src : Register = Src(w), port : Register = Dest(Width::Byte)];
+
+    // Byte results take the whole word register, word results also take the next one
+    muli2r Binary(Mul(op), Imm(src), Reg(dest)) => 0x70 sized(src.width()) [op : MulOp = Op, src : Immediate = Imm(w), dest : Register = Dest(w)] if Self::is_wide_dest(dest);
+    mulip2r Binary(Mul(op), ImmPtr(src), Reg(dest)) => 0x72 sized(dest.width()) [op : MulOp = Op, src : Immediate = Imm(Width::Word), dest : Register = Dest(w)] if Self::is_wide_dest(dest);
+    mulr2r Binary(Mul(op), Reg(src), Reg(dest)) => 0x74 sized(src.width()) [op : MulOp = Op, src : Register = Src(w), dest : Register = Dest(w)] if Self::is_wide_dest(dest);
+    mulrp2r Binary(Mul(op), RegPtr(src), Reg(dest)) => 0x76 sized(dest.width()) [op : MulOp = Op, src : Register = Src(Width::Word), dest : Register = Dest(w)] if Self::is_wide_dest(dest);
+
+    block Block(op, width, src, dest, count) => 0x80 sized(*width) [
+        op : BlockOp = Op,
+        width : Width = Width,
+        src : Register = Src(if op.has_src() { Width::Word } else { w }),
+        dest : Register = Dest(Width::Word),
+        count : Register = Extra(Width::Word),
+    ] if Self::is_own_count(count, src, dest);
+
+    // Same layout as the Mov opcodes
+    xchgr2r Binary(Xchg, Reg(src), Reg(dest)) => 0x90 sized(src.width()) [src : Register = Src(w), dest : Register = Dest(w)];
+    xchgr2rp Binary(Xchg, Reg(src), RegPtr(dest)) => 0x92 sized(src.width()) [src : Register = Src(w), dest : Register = Dest(Width::Word)];
+    xchgr2ip Binary(Xchg, Reg(src), ImmPtr(dest)) => 0x94 sized(src.width()) [src : Register = Src(w), dest : Immediate = Imm(Width::Word)];
+    cmpxchgr2r Binary(Cmpxchg, Reg(src), Reg(dest)) => 0x98 sized(src.width()) [src : Register = Src(w), dest : Register = Dest(w)];
+    cmpxchgr2rp Binary(Cmpxchg, Reg(src), RegPtr(dest)) => 0x9A sized(src.width()) [src : Register = Src(w), dest : Register = Dest(Width::Word)];
+    cmpxchgr2ip Binary(Cmpxchg, Reg(src), ImmPtr(dest)) => 0x9C sized(src.width()) [src : Register = Src(w), dest : Immediate = Imm(Width::Word)];
+
+    // `[base + index * scale + disp]`, mirrors the Mov and Alu opcodes that take a single memory operand
+    movi2m Binary(Mov, Imm(src), Indexed(dest)) => 0xA0 sized(src.width()) [src : Immediate = Imm(w), dest : Address = Addr];
+    movm2r Binary(Mov, Indexed(src), Reg(dest)) => 0xA2 sized(dest.width()) [src : Address = Addr, dest : Register = Dest(w)];
+    movr2m Binary(Mov, Reg(src), Indexed(dest)) => 0xA4 sized(src.width()) [src : Register = Src(w), dest : Address = Addr];
+    movxm2r Binary(Movx(ext), Indexed(src), Reg(dest)) => 0xA6 [ext : Extension = Op, src : Address = Addr, dest : Register = Dest(Width::Word)];
+    alui2m Binary(Alu(op), Imm(src), Indexed(dest)) => 0xA8 sized(src.width()) [op : AluOp = Op, src : Immediate = Imm(w), dest : Address = Addr];
+    alum2r Binary(Alu(op), Indexed(src), Reg(dest)) => 0xAA sized(dest.width()) [op : AluOp = Op, src : Address = Addr, dest : Register = Dest(w)];
+    alur2m Binary(Alu(op), Reg(src), Indexed(dest)) => 0xAC sized(src.width()) [op : AluOp = Op, src : Register = Src(w), dest : Address = Addr];
+    mulm2r Binary(Mul(op), Indexed(src), Reg(dest)) => 0xAE sized(dest.width()) [op : MulOp = Op, src : Address = Addr, dest : Register = Dest(w)] if Self::is_wide_dest(dest);
+    jmpm Jmp(cond, Indexed(target)) => 0xB1 [cond : Condition = Op, target : Address = Addr];
+    callm Call(Indexed(target)) => 0xB2 [target : Address = Addr];
+    xchgr2m Binary(Xchg, Reg(src), Indexed(dest)) => 0xB4 sized(src.width()) [src : Register = Src(w), dest : Address = Addr];
+    cmpxchgr2m Binary(Cmpxchg, Reg(src), Indexed(dest)) => 0xB6 sized(src.width()) [src : Register = Src(w), dest : Address = Addr];
+
+    // Relative to the next instruction, so the code runs wherever it is loaded
+    jmprel Jmp(cond, Rel(target)) => 0xB8 [cond : Condition = Op, target : Immediate = Imm(Width::Word)];
+    callrel Call(Rel(target)) => 0xB9 [target : Immediate = Imm(Width::Word)];
+    movrel2r Binary(Mov, Rel(src), Reg(dest)) => 0xBA [src : Immediate = Imm(Width::Word), dest : Register = Dest(Width::Word)];
+}
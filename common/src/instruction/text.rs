@@ -0,0 +1,245 @@
+#[allow(unused_imports)]
+use crate::prelude::*;
+
+impl Instruction {
+    /// The `.b` or `.w` sasm needs to pick the width of the data, when the operands don't say it.
+    /// Immediates written to memory take the smallest width that fits them, and pushed ones are words
+    fn width_tag(&self) -> Option<Width> {
+        match self {
+            Self::BinaryMem(_, width, _, _) | Self::UnaryMem(_, width, _) => Some(*width),
+            Self::Binary(_, Operand::Imm(value), dest) if dest.width().is_none() => {
+                (value.width() != Width::smallest_that_fits(value.literal())).then_some(value.width())
+            },
+            Self::Push(Operand::Imm(value)) if value.width() == Width::Byte => Some(Width::Byte),
+            _ => None,
+        }
+    }
+
+    fn operands(&self) -> Vec<String> {
+        match self {
+            Self::Nop | Self::Ret | Self::Iret | Self::Sti | Self::Cli | Self::Hlt | Self::Wfi => vec![],
+            Self::Binary(_, src, dest) | Self::BinaryMem(_, _, src, dest) => vec![src.to_string(), dest.to_string()],
+            Self::Unary(_, operand) | Self::UnaryMem(_, _, operand) | Self::Jmp(_, operand) | Self::Call(operand)
+            | Self::Push(operand) | Self::Pop(operand) => vec![operand.to_string()],
+            Self::Int(vector) => vec![vector.to_string()],
+            Self::In(port, dest) => vec![port.to_string(), dest.to_string()],
+            Self::Out(src, port) => vec![src.to_string(), port.to_string()],
+            Self::Block(_, _, src, dest, count) => vec![src.to_string(), dest.to_string(), count.to_string()],
+        }
+    }
+
+    /// Tagged immediates take the width of the tag, otherwise the tag must match the width of the data.
+    /// Pushed immediates are words unless tagged, like in sasm
+    fn from_parts(mnemonic : Mnemonic, width : Option<Width>, operands : &[Operand]) -> Result<Self> {
+        use Mnemonic::*;
+        let tagged = |operand : &Operand| match (operand, width) {
+            (Operand::Imm(value), Some(width)) => Ok(Operand::Imm(value.resize(width)?)),
+            _ => Ok(*operand),
+        };
+        let unexpected = |operand : &Operand| Error::UnexpectedToken(mnemonic.to_string(), operand.to_string());
+
+        let instr = match (mnemonic, operands) {
+            (_, [src, dest]) if src.is_memory() && dest.is_memory() && mnemonic.binary_op().is_some() => {
+                let width = width.ok_or_else(|| Error::AmbiguousWidth(mnemonic.to_string()))?;
+                Self::binary_mem(mnemonic.binary_op().unwrap(), width, *src, *dest)?
+            },
+            (_, [src, dest]) if mnemonic.binary_op().is_some() => Self::binary(mnemonic.binary_op().unwrap(), tagged(src)?, *dest)?,
+            (Unary(op), [dest]) if dest.is_memory() => {
+                let width = width.ok_or_else(|| Error::AmbiguousWidth(mnemonic.to_string()))?;
+                Self::unary_mem(op, width, *dest)?
+            },
+            (Unary(op), [dest]) => Self::unary(op, *dest)?,
+            (Push, [Operand::Imm(value)]) => return Self::push(Operand::Imm(value.resize(width.unwrap_or(Width::Word))?)),
+            _ if width.is_some() => return Err(Error::UnexpectedToken(mnemonic.to_string(), format!("{:?}", width.unwrap()))),
+
+            (Nop, []) => Self::nop()?,
+            (Jmp(cond), [target]) => Self::jmp(cond, *target)?,
+            (Call, [target]) => Self::call(*target)?,
+            (Ret, []) => Self::ret()?,
+            (Int, [Operand::Imm(vector)]) => Self::int(*vector)?,
+            (Iret, []) => Self::iret()?,
+            (Sti, []) => Self::sti()?,
+            (Cli, []) => Self::cli()?,
+            (Hlt, []) => Self::hlt()?,
+            (Wfi, []) => Self::wfi()?,
+            (Push, [src]) => Self::push(*src)?,
+            (Pop, [dest]) => Self::pop(*dest)?,
+            (In, [port, Operand::Reg(dest)]) => Self::input(*port, *dest)?,
+            (Out, [Operand::Reg(src), port]) => Self::output(*src, *port)?,
+            (Block(op, width), [Operand::Reg(src), Operand::Reg(dest), Operand::Reg(count)]) => Self::block(op, width, *src, *dest, *count)?,
+            (_, [.., last]) => return Err(unexpected(last)),
+            (_, []) => return Err(Error::MissingToken(mnemonic.to_string())),
+        };
+
+        match width {
+            Some(width) if instr.data_width() != Some(width) => Err(Error::InvalidOperands(instr)),
+            _ => Ok(instr),
+        }
+    }
+}
+
+/// The assembly sasm reads, without labels
+impl core::fmt::Display for Instruction {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{}", self.mnemonic())?;
+        match self.width_tag() {
+            Some(Width::Byte) => write!(f, ".b")?,
+            Some(Width::Word) => write!(f, ".w")?,
+            None => (),
+        }
+
+        let operands = self.operands();
+        if !operands.is_empty() {
+            write!(f, " {}", operands.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+/// Reads what `Display` writes. Hexadecimal immediates take their width from their number of digits,
+/// other numbers the smallest width that fits them
+impl core::str::FromStr for Instruction {
+    type Err = Error;
+
+    fn from_str(s : &str) -> Result<Self> {
+        let s = s.trim();
+        let (name, operands) = s.split_once(char::is_whitespace).unwrap_or((s, ""));
+        let (name, width) = match name.split_once('.') {
+            Some((name, "b")) => (name, Some(Width::Byte)),
+            Some((name, "w")) => (name, Some(Width::Word)),
+            Some((name, tag)) => return Err(Error::UnexpectedToken(name.to_string(), tag.to_string())),
+            None => (name, None),
+        };
+        let Some(mnemonic) = Mnemonic::from(name) else {
+            return Err(Error::UnknownInstruction(name.to_string()));
+        };
+
+        let operands = match operands.trim() {
+            "" => vec![],
+            operands => operands.split(',').map(|operand| parse_operand(operand.trim(), name)).collect::<Result<Vec<_>>>()?,
+        };
+        if operands.len() < mnemonic.arity() {
+            return Err(Error::MissingToken(name.to_string()));
+        }
+        Instruction::from_parts(mnemonic, width, &operands)
+    }
+}
+
+/// Reads what `Display` writes, hexadecimal or not like in instructions
+impl core::str::FromStr for Immediate {
+    type Err = Error;
+
+    fn from_str(s : &str) -> Result<Self> {
+        parse_number(s.trim(), "immediate")
+    }
+}
+
+fn parse_operand(s : &str, ctx : &str) -> Result<Operand> {
+    let unexpected = || Error::UnexpectedToken(ctx.to_string(), s.to_string());
+    if let Some(inner) = s.strip_prefix('[').and_then(|s| s.strip_suffix(']')) {
+        parse_memory(inner, ctx)
+    } else if let Some(inner) = s.strip_prefix('(').and_then(|s| s.strip_suffix(')')) {
+        match parse_memory(inner, ctx)? {
+            Operand::Indexed(Address { base : Register::RIP, index : None, disp }) => Ok(Operand::Rel(disp)),
+            _ => Err(unexpected()),
+        }
+    } else if let Some(reg) = Register::from(s) {
+        Ok(Operand::Reg(reg))
+    } else {
+        parse_number(s, ctx).map(Operand::Imm)
+    }
+}
+
+fn parse_number(s : &str, ctx : &str) -> Result<Immediate> {
+    let (negative, unsigned) = s.strip_prefix('-').map_or((false, s), |unsigned| (true, unsigned));
+    let (digits, radix) = match unsigned.get(..2) {
+        Some("0x") => (&unsigned[2..], 16),
+        Some("0o") => (&unsigned[2..], 8),
+        Some("0b") => (&unsigned[2..], 2),
+        _ => (unsigned, 10),
+    };
+    let value = u32::from_str_radix(digits, radix).map_err(|_| Error::UnexpectedToken(ctx.to_string(), s.to_string()))? as i64;
+    let value = if negative { -value } else { value };
+    let width = if radix == 16 && digits.len() <= 2 { Width::Byte } else if radix == 16 { Width::Word } else { Width::smallest_that_fits(value) };
+    Immediate::signed(width, value)
+}
+
+/// `base + index*scale + disp` like sasm, but the base must come first
+fn parse_memory(s : &str, ctx : &str) -> Result<Operand> {
+    let unexpected = |tok : &str| Error::UnexpectedToken(ctx.to_string(), tok.to_string());
+
+    let mut terms = Vec::new();
+    let (mut negative, mut start) = (false, 0);
+    for (idx, c) in s.char_indices().filter(|(_, c)| *c == '+' || *c == '-') {
+        terms.push((negative, s[start..idx].trim()));
+        (negative, start) = (c == '-', idx + 1);
+    }
+    terms.push((negative, s[start..].trim()));
+
+    let mut regs = Vec::new();
+    let mut disp : Option<u16> = None;
+    for (negative, term) in terms {
+        let (name, scale) = term.split_once('*').map_or((term, None), |(name, scale)| (name.trim(), Some(scale.trim())));
+        match (Register::from(name), scale) {
+            (Some(reg), _) if negative => return Err(unexpected(&reg.to_string())),
+            (Some(reg), None) => regs.push((reg, None)),
+            (Some(reg), Some(scale)) => regs.push((reg, Some(scale.parse::<u8>().map_err(|_| unexpected(scale))?))),
+            (None, None) => {
+                let value = parse_number(term, ctx)?.get_value();
+                let disp = disp.get_or_insert(0);
+                *disp = if negative { disp.wrapping_sub(value) } else { disp.wrapping_add(value) };
+            },
+            (None, Some(_)) => return Err(unexpected(term)),
+        }
+    }
+
+    match (regs.as_slice(), disp) {
+        ([], Some(addr)) => Ok(Operand::ImmPtr(Immediate::word(addr))),
+        ([(reg, None)], None) => Ok(Operand::RegPtr(*reg)),
+        ([(base, None)], disp) => Ok(Operand::Indexed(Address::new(*base, None, disp.unwrap_or(0)))),
+        ([(base, None), (index, scale)], disp) => Ok(Operand::Indexed(Address::new(*base, Some((*index, scale.unwrap_or(1))), disp.unwrap_or(0)))),
+        _ => Err(unexpected(s)),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn display() {
+        let cases = [
+            (Instruction::movi2rp(Immediate::word(0x600D), Register::r0()), "mov 0x600D, [r0]"),
+            (Instruction::movi2rp(Immediate::word(0x0060), Register::r0()), "mov.w 0x0060, [r0]"),
+            (Instruction::movi2r(Immediate::byte(0x60), Register::rb1()), "mov 0x60, rb1"),
+            (Instruction::movip2rp(Width::Byte, Immediate::word(0xF337), Register::r1()), "mov.b [0xF337], [r1]"),
+            (Instruction::movm2r(Address::new(Register::r0(), Some((Register::r1(), 2)), 0x10), Register::r2()), "mov [r0 + r1*2 + 0x0010], r2"),
+            (Instruction::movm2r(Address::new(Register::r0(), None, 0), Register::r2()), "mov [r0 + 0x0000], r2"),
+            (Instruction::alur2r(AluOp::Sbb, Register::rsh(), Register::rip()), "sbb rsh, rip"),
+            (Instruction::jmprel(Condition::Zero, Immediate::word(0xFFF0)), "jz (rip + 0xFFF0)"),
+            (Instruction::pushi(Immediate::byte(0x60)), "push.b 0x60"),
+            (Instruction::block(BlockOp::Fill, Width::Word, Register::r3(), Register::r1(), Register::r2()), "stosw r3, r1, r2"),
+            (Instruction::ini(Immediate::byte(0x10), Register::rb0()), "in 0x10, rb0"),
+            (Instruction::ret(), "ret"),
+        ];
+        for (instr, s) in cases {
+            let instr = instr.unwrap();
+            assert_eq!(instr.to_string(), s);
+            assert_eq!(s.parse::<Instruction>(), Ok(instr), "{s}");
+        }
+    }
+
+    #[test]
+    fn parse() {
+        assert_eq!("not.w [0x8000]".parse(), Instruction::unaryip(UnaryOp::Not, Width::Word, Immediate::word(0x8000)));
+        assert_eq!("mov [r0 + 16], r1".parse(), Instruction::movm2r(Address::new(Register::r0(), None, 0x10), Register::r1()));
+        assert_eq!("mov [r0], [r1]".parse::<Instruction>(), Err(Error::AmbiguousWidth("mov".to_string())));
+        assert_eq!("mov.b r0, [r1]".parse::<Instruction>(), Err(Error::InvalidOperands(Instruction::Binary(BinaryOp::Mov, Operand::Reg(Register::r0()), Operand::RegPtr(Register::r1())))));
+        assert_eq!("mov 0x10000, r0".parse::<Instruction>(), Err(Error::NumberOOB(0x10000, Width::Word)));
+        assert_eq!("mov -0x81, rb0".parse::<Instruction>(), Err(Error::NumberOOB(-0x81, Width::Byte)));
+        assert_eq!("mov -0x0002, r0".parse::<Instruction>().map(|instr| instr.to_string()), Ok("mov -0x0002, r0".to_string()));
+        assert_eq!("mov r0".parse::<Instruction>(), Err(Error::MissingToken("mov".to_string())));
+        assert_eq!("jmp.w r0".parse::<Instruction>(), Err(Error::UnexpectedToken("jmp".to_string(), format!("{:?}", Width::Word))));
+        assert_eq!("movs r0, r1, r2".parse::<Instruction>(), Err(Error::UnknownInstruction("movs".to_string())));
+    }
+}
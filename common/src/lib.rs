@@ -1,7 +1,14 @@
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
+extern crate alloc;
+
 mod instruction;
 mod value;
+mod flags;
 pub mod utils;
+#[cfg(feature = "serde")]
+mod serialize;
 
 pub use instruction::*;
 pub use value::*;
+pub use flags::*;
 pub use utils::prelude;
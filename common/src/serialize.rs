@@ -0,0 +1,77 @@
+//! With the `serde` feature, instructions, registers and immediates are serialized as the text sasm reads,
+//! which is what their `Display` writes and `FromStr` reads back:
+//! - `Instruction`: its canonical assembly, e.g. `"mov.w 0x0060, [r0 + r1*2 + 0x0010]"`
+//! - `Register`: its name, e.g. `"rb0"` or `"rip"`
+//! - `Immediate`: hexadecimal with as many digits as its width holds, e.g. `"0x60"` or `"-0x0002"`
+//!
+//! `Width` and `ParamIdx` are their variant names, e.g. `"Byte"` or `"FirstImm"`.
+//! Reading anything back checks it like sasm does, so invalid instructions can't be deserialized
+
+#[allow(unused_imports)]
+use crate::prelude::*;
+
+macro_rules! as_text {
+    ($($Type:ty),+ $(,)?) => {
+        $(
+            impl serde::Serialize for $Type {
+                fn serialize<S : serde::Serializer>(&self, serializer : S) -> core::result::Result<S::Ok, S::Error> {
+                    serializer.collect_str(self)
+                }
+            }
+
+            impl<'de> serde::Deserialize<'de> for $Type {
+                fn deserialize<D : serde::Deserializer<'de>>(deserializer : D) -> core::result::Result<Self, D::Error> {
+                    let s = String::deserialize(deserializer)?;
+                    s.parse().map_err(serde::de::Error::custom)
+                }
+            }
+        )+
+    };
+}
+
+as_text!(Instruction, Register, Immediate);
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::ParamIdx;
+
+    #[test]
+    fn instruction() {
+        let instructions = [
+            Instruction::movi2rp(Immediate::word(0x0060), Register::r0()),
+            Instruction::movi2r(Immediate::signed(Width::Word, -2).unwrap(), Register::r0()),
+            Instruction::alum2r(AluOp::Sub, Address::new(Register::r0(), Some((Register::r1(), 4)), 0xFFFE), Register::r2()),
+            Instruction::unaryip(UnaryOp::Not, Width::Word, Immediate::word(0x8000)),
+            Instruction::jmprel(Condition::NotZero, Immediate::word(0xFFF0)),
+            Instruction::pushi(Immediate::byte(0x60)),
+            Instruction::block(BlockOp::Copy, Width::Byte, Register::r0(), Register::r1(), Register::r2()),
+        ];
+        for instr in instructions {
+            let instr = instr.unwrap();
+            let json = serde_json::to_string(&instr).unwrap();
+            assert_eq!(json, format!("\"{instr}\""));
+            assert_eq!(serde_json::from_str::<Instruction>(&json).unwrap(), instr, "{json}");
+        }
+
+        assert!(serde_json::from_str::<Instruction>("\"mov [r0], [r1]\"").is_err());
+        assert!(serde_json::from_str::<Instruction>("\"int 0x600D\"").is_err());
+    }
+
+    #[test]
+    fn values() {
+        assert_eq!(serde_json::to_string(&Register::rb3()).unwrap(), "\"rb3\"");
+        assert_eq!(serde_json::from_str::<Register>("\"RIP\"").unwrap(), Register::rip());
+        assert!(serde_json::from_str::<Register>("\"r11\"").is_err());
+
+        let imm = Immediate::signed(Width::Byte, -2).unwrap();
+        assert_eq!(serde_json::to_string(&imm).unwrap(), "\"-0x02\"");
+        assert!(serde_json::from_str::<Immediate>("\"-0x02\"").unwrap().is_signed());
+        assert_eq!(serde_json::from_str::<Immediate>("\"0x0060\"").unwrap(), Immediate::word(0x60));
+        assert!(serde_json::from_str::<Immediate>("\"0x10000\"").is_err());
+
+        assert_eq!(serde_json::to_string(&Width::Word).unwrap(), "\"Word\"");
+        assert_eq!(serde_json::to_string(&ParamIdx::FirstImm).unwrap(), "\"FirstImm\"");
+        assert_eq!(serde_json::from_str::<Width>("\"Byte\"").unwrap(), Width::Byte);
+    }
+}
@@ -1,15 +1,21 @@
 pub mod prelude {
-    pub use crate::{Instruction, Value, Width, Register, Immediate, utils::{Error, Result}};
+    pub use crate::{Instruction, Operand, Address, BinaryOp, Mnemonic, Extension, AluOp, UnaryOp, MulOp, BlockOp, Condition, Value, Width, Register, Immediate, Flags, utils::{Error, Result}};
+    /// What the std prelude would bring, for the crate itself
+    #[allow(unused_imports)]
+    pub(crate) use alloc::{format, vec, vec::Vec, string::{String, ToString}};
 }
 use crate::prelude::*;
 
 #[derive(thiserror::Error, Debug, Clone, PartialEq)]
 pub enum Error {
-    #[error("incompatible operands: {0:?}")]
+    #[error("incompatible operands: \"{0}\"")]
     InvalidOperands(Instruction),
 
     #[error("number out of bounds: {0} doesn't fit {1:?}")]
-    NumberOOB(u64, Width),
+    NumberOOB(i64, Width),
+
+    #[error("number out of bounds: {0} doesn't fit any width")]
+    LiteralOOB(String),
 
     #[error("reached EOL")]
     EOL,
@@ -23,12 +29,24 @@ pub enum Error {
     #[error("unknown instruction \"{0}\"")]
     UnknownInstruction(String),
 
+    #[error("unknown register \"{0}\"")]
+    UnknownRegister(String),
+
+    #[error("ambiguous width in instruction \"{0}\", tag it with .b or .w")]
+    AmbiguousWidth(String),
+
     #[error("label not defined \"{0}\"")]
     LabelNotDefined(String),
 
     #[error("missing opcode")]
     NoOpcode,
 
+    #[error("missing operation")]
+    NoOperation,
+
+    #[error("no such operation \"{1:#04x}\" for opcode \"{0:#04x}\"")]
+    NoSuchOperation(u8, u8),
+
     #[error("missing registers")]
     NoRegs,
 
@@ -38,7 +56,25 @@ pub enum Error {
     #[error("no such opcode \"{0:#04x}\"")]
     NoSuchOpcode(u8),
 
+    #[error("buffer of {0} bytes can't hold an instruction of {1}")]
+    BufferTooSmall(usize, usize),
+
+    #[error("stack overflow pushing at {0:#06x}")]
+    StackOverflow(u16),
+
+    #[error("stack underflow popping at {0:#06x}")]
+    StackUnderflow(u16),
+
+    #[error("no device at port {0:#04x}")]
+    NoSuchPort(u8),
+
+    #[error("division by zero")]
+    DivisionByZero,
+
+    #[error("division overflow: quotient doesn't fit {0:?}")]
+    DivisionOverflow(Width),
+
     #[error("{0}")]
     Misc(String),
 }
-pub type Result<T> = std::result::Result<T, Error>;
+pub type Result<T> = core::result::Result<T, Error>;
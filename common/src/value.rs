@@ -1,27 +1,28 @@
 #[allow(unused_imports)]
 use crate::prelude::*;
 
-pub trait Value : std::fmt::Debug + std::fmt::Display + Clone + Copy + PartialEq {
+pub trait Value : core::fmt::Debug + core::fmt::Display + Clone + Copy + PartialEq {
    fn width(&self) -> Width; 
 }
 
 #[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Width {
     Byte, Word,
 }
 
 impl Width {
-    pub fn smallest_that_fits(value : u16) -> Self {
-        <u16 as TryInto<u8>>::try_into(value).map(|_| Self::Byte)
-            .or(<u16 as TryInto<u16>>::try_into(value).map(|_| Self::Word))
-            .unwrap()
+    /// Words when not even they fit it
+    pub fn smallest_that_fits(value : i64) -> Self {
+        if Self::Byte.fits(value) { Self::Byte } else { Self::Word }
     }
 
-    pub fn fits(&self, value : u16) -> bool {
+    /// Whether it fits either signed or unsigned
+    pub fn fits(&self, value : i64) -> bool {
         use Width::*;
         match self {
-            Byte => <u16 as TryInto<u8>>::try_into(value).is_ok(),
-            Word => <u16 as TryInto<u16>>::try_into(value).is_ok(),
+            Byte => (i8::MIN as i64..=u8::MAX as i64).contains(&value),
+            Word => (i16::MIN as i64..=u16::MAX as i64).contains(&value),
         }
     }
 
@@ -135,6 +136,22 @@ impl Register {
         }
     }
     
+    /// The same general purpose register viewed with another width
+    pub fn with_width(&self, width : Width) -> Self {
+        match self {
+            Self::R(_, idx) => Self::r(width, *idx),
+            _ => *self,
+        }
+    }
+
+    /// The general purpose register right after this one, which holds the upper half of a widened result
+    pub fn next(&self) -> Option<Self> {
+        match self {
+            Self::R(width, idx) if *idx < 10 => Some(Self::r(*width, idx + 1)),
+            _ => None,
+        }
+    }
+
     pub fn as_dest(&self) -> u8 {
         self.as_src() << 4
     }
@@ -156,37 +173,65 @@ impl Value for Register {
     }
 }
 
-impl std::fmt::Display for Register {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{self:?}")
+/// The name sasm uses for it
+impl core::fmt::Display for Register {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        use Register::*;
+        match self {
+            RINFO => write!(f, "rinfo"),
+            RIP => write!(f, "rip"),
+            Flags => write!(f, "flags"),
+            RSH => write!(f, "rsh"),
+            RSB => write!(f, "rsb"),
+            R(Width::Byte, idx) => write!(f, "rb{idx}"),
+            R(Width::Word, idx) => write!(f, "r{idx}"),
+        }
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq)]
+impl core::str::FromStr for Register {
+    type Err = Error;
+
+    fn from_str(s : &str) -> Result<Self> {
+        Self::from(s).ok_or_else(|| Error::UnknownRegister(s.to_string()))
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
 pub struct Immediate {
     width : Width,
     value : u16,
+    /// Whether it was written as a negative number
+    signed : bool,
 }
 
 impl Immediate {
     pub fn new(width : Width, value : u16) -> Result<Self> {
-        if width.fits(value) {
-            Ok(Self { width, value })
-        } else {
-            Err(Error::NumberOOB(value as u64, width))
+        Self::signed(width, value as i64)
+    }
+
+    /// Negative values are stored in two's complement, and remembered as signed
+    pub fn signed(width : Width, value : i64) -> Result<Self> {
+        if !width.fits(value) {
+            return Err(Error::NumberOOB(value, width));
         }
+        let bits = match width {
+            Width::Byte => value as u8 as u16,
+            Width::Word => value as u16,
+        };
+        Ok(Self { width, value : bits, signed : value < 0 })
     }
 
     pub fn new_unchecked(width : Width, value : u16) -> Self {
-        Self { width, value }
+        Self { width, value, signed : false }
     }
 
     pub fn byte(value : u8) -> Self {
-        Self { width: Width::Byte, value: value as u16 }
+        Self { width: Width::Byte, value: value as u16, signed : false }
     }
 
     pub fn word(value : u16) -> Self {
-        Self { width: Width::Word, value: value as u16 }
+        Self { width: Width::Word, value, signed : false }
     }
 
     pub fn get_byte(&self, idx : u8) -> u8 {
@@ -194,12 +239,37 @@ impl Immediate {
     }
 
     pub fn get_word(&self, idx : u8) -> u16 {
-        (self.value >> (idx * 16)) as u16
+        self.value >> (idx * 16)
     }
 
     pub fn get_value(&self) -> u16 {
         self.value
     }
+
+    pub fn is_signed(&self) -> bool {
+        self.signed
+    }
+
+    /// The value as it was written, sign extended if it was negative
+    pub fn literal(&self) -> i64 {
+        match (self.signed, self.width) {
+            (true, Width::Byte) => self.value as u8 as i8 as i64,
+            (true, Width::Word) => self.value as i16 as i64,
+            (false, _) => self.value as i64,
+        }
+    }
+
+    /// The same literal with another width
+    pub fn resize(&self, width : Width) -> Result<Self> {
+        Self::signed(width, self.literal())
+    }
+}
+
+/// The sign only says how it was written, the same bits are the same immediate
+impl PartialEq for Immediate {
+    fn eq(&self, other : &Self) -> bool {
+        self.width == other.width && self.value == other.value
+    }
 }
 
 impl Value for Immediate {
@@ -208,11 +278,14 @@ impl Value for Immediate {
     }
 }
 
-impl std::fmt::Display for Immediate {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+/// In hexadecimal with as many digits as its width holds, signed ones with a minus and their magnitude
+impl core::fmt::Display for Immediate {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        let sign = if self.signed { "-" } else { "" };
+        let magnitude = self.literal().unsigned_abs();
         match self.width {
-            Width::Byte => write!(f, "{:#04}", self.value as u8),
-            Width::Word => write!(f, "{:#04}", self.value as u16),
+            Width::Byte => write!(f, "{sign}{magnitude:#04X}"),
+            Width::Word => write!(f, "{sign}{magnitude:#06X}"),
         }
     }
 }
@@ -6,7 +6,7 @@ use std::io::{Read, Write};
 use common::prelude::*;
 
 use clap::Parser;
-use vm::VM;
+use vm::{VM, StopReason};
 
 /// Assembler for SimpleASM
 #[derive(Parser, Debug)]
@@ -15,14 +15,18 @@ pub struct Args {
     /// Path to the rom file
     rom_path : String,
 
-    /// Number of instructions to execute
+    /// Maximum number of instructions to execute, runs until the CPU stops otherwise
     #[arg(long)]
-    reps : usize,
+    reps : Option<usize>,
 
-    /// Size of the RAM available during execution
+    /// Size of the RAM available during execution, it must reach the vector table at the top of the address space
     #[arg(long, default_value_t = 0x8000)]
     ram_size : usize,
 
+    /// Initial address of the stack, it grows upwards through the RAM
+    #[arg(long, default_value_t = 0x8000)]
+    stack : u16,
+
     /// Enable debugging
     #[arg(long, default_value_t = false)]
     debug : bool,
@@ -32,20 +36,39 @@ fn main() -> Result<()> {
     let args = Args::parse();
 
     let rom = read_bytes(&args.rom_path)?;
-    let mut vm = VM::new(rom, args.ram_size);
+    let mut vm = VM::new(rom, args.ram_size)?;
+    vm.boot(args.stack);
+
+    let reason = if args.debug {
+        let mut executed = 0;
+        loop {
+            if args.reps.is_some_and(|reps| executed >= reps) {
+                break StopReason::Limit;
+            }
 
-    for _ in 0..args.reps {
-        if args.debug {
-            println!("{:?}", vm.regs());
+            println!("{:?} {}", vm.regs(), vm.flags());
             print!("> ");
             std::io::stdout().flush().unwrap();
-            std::io::stdin().read(&mut [0u8]).unwrap();
+            let read = std::io::stdin().read(&mut [0u8]).map_err(|err| Error::Misc(err.to_string()))?;
+            if read == 0 {
+                // Nothing left to step with, as if no more instructions were allowed
+                break StopReason::Limit;
+            }
+
+            match vm.run(Some(1)) {
+                StopReason::Limit => executed += 1,
+                reason => break reason,
+            }
         }
-        vm.execute_next()?;
-    }
-    println!("Finished with: {:?}", vm.regs());
+    } else {
+        vm.run(args.reps)
+    };
+    println!("Stopped ({reason}) with: {:?} {}", vm.regs(), vm.flags());
 
-    Ok(())
+    match reason {
+        StopReason::Fault(err) => Err(err),
+        _ => Ok(()),
+    }
 }
 
 fn read_bytes(fpath : &str) -> Result<Vec<u8>> {
@@ -0,0 +1,169 @@
+#[allow(unused_imports)]
+use common::prelude::*;
+
+fn masks(width : Width) -> (u32, u32) {
+    match width {
+        Width::Byte => (0xFF, 0x80),
+        Width::Word => (0xFFFF, 0x8000),
+    }
+}
+
+fn bits(width : Width) -> u32 {
+    width.len() as u32 * 8
+}
+
+fn set_result_flags(flags : &mut Flags, width : Width, res : u32) {
+    let (_, sign) = masks(width);
+    flags.set_zero(res == 0);
+    flags.set_sign(res & sign != 0);
+}
+
+fn add(width : Width, dest : u16, src : u16, carry_in : bool, flags : &mut Flags) -> u16 {
+    let (mask, sign) = masks(width);
+    let (a, b) = (dest as u32 & mask, src as u32 & mask);
+    let raw = a + b + carry_in as u32;
+    let res = raw & mask;
+
+    set_result_flags(flags, width, res);
+    flags.set_carry(raw > mask);
+    flags.set_overflow((a ^ res) & (b ^ res) & sign != 0);
+    res as u16
+}
+
+fn sub(width : Width, dest : u16, src : u16, borrow_in : bool, flags : &mut Flags) -> u16 {
+    let (mask, sign) = masks(width);
+    let (a, b) = (dest as u32 & mask, src as u32 & mask);
+    let res = a.wrapping_sub(b).wrapping_sub(borrow_in as u32) & mask;
+
+    set_result_flags(flags, width, res);
+    flags.set_carry(a < b + borrow_in as u32);
+    flags.set_overflow((a ^ b) & (a ^ res) & sign != 0);
+    res as u16
+}
+
+fn logic(width : Width, res : u32, flags : &mut Flags) -> u16 {
+    let (mask, _) = masks(width);
+    let res = res & mask;
+
+    set_result_flags(flags, width, res);
+    flags.set_carry(false);
+    flags.set_overflow(false);
+    res as u16
+}
+
+/// Shifts and rotates set the carry to the last bit shifted out and the overflow if the sign changed
+fn shift(op : &AluOp, width : Width, dest : u16, count : u16, flags : &mut Flags) -> u16 {
+    let (mask, sign) = masks(width);
+    let bits = bits(width);
+    let a = dest as u32 & mask;
+    // Shifting more than the width clears it all the same, while rotating goes around
+    let count = count as u32 & mask;
+    let rotate = count % bits;
+    let count = count.min(bits + 1);
+
+    let (res, carry) = match op {
+        AluOp::Shl => {
+            let raw = a << count;
+            (raw & mask, count > 0 && (raw >> bits) & 1 != 0)
+        },
+        AluOp::Shr => (a >> count, count > 0 && (a >> (count - 1)) & 1 != 0),
+        AluOp::Sar => {
+            let signed = if a & sign != 0 { a | !mask } else { a } as i32;
+            let res = (signed >> count.min(bits - 1)) as u32 & mask;
+            (res, count > 0 && (signed >> (count - 1).min(bits - 1)) & 1 != 0)
+        },
+        AluOp::Rol => {
+            let res = ((a << rotate) | (a >> (bits - rotate))) & mask;
+            (res, rotate > 0 && res & 1 != 0)
+        },
+        AluOp::Ror => {
+            let res = ((a >> rotate) | (a << (bits - rotate))) & mask;
+            (res, rotate > 0 && res & sign != 0)
+        },
+        _ => unreachable!("{op:?}"),
+    };
+
+    set_result_flags(flags, width, res);
+    flags.set_carry(carry);
+    flags.set_overflow((a ^ res) & sign != 0);
+    res as u16
+}
+
+/// Computes `dest op src`, updating `flags` accordingly. The caller decides whether to store the result
+pub fn alu(op : &AluOp, width : Width, dest : u16, src : u16, flags : &mut Flags) -> u16 {
+    let carry = flags.carry();
+    match op {
+        AluOp::Add => add(width, dest, src, false, flags),
+        AluOp::Sub | AluOp::Cmp => sub(width, dest, src, false, flags),
+        AluOp::Adc => add(width, dest, src, carry, flags),
+        AluOp::Sbb => sub(width, dest, src, carry, flags),
+        AluOp::And | AluOp::Test => logic(width, (dest & src) as u32, flags),
+        AluOp::Or => logic(width, (dest | src) as u32, flags),
+        AluOp::Xor => logic(width, (dest ^ src) as u32, flags),
+        AluOp::Shl | AluOp::Shr | AluOp::Sar | AluOp::Rol | AluOp::Ror
+            => shift(op, width, dest, src, flags),
+    }
+}
+
+/// Computes `op dest`, updating `flags` accordingly
+pub fn unary(op : &UnaryOp, width : Width, dest : u16, flags : &mut Flags) -> u16 {
+    // Like in x86, inc and dec leave the carry untouched
+    let carry = flags.carry();
+    match op {
+        UnaryOp::Inc => {
+            let res = add(width, dest, 1, false, flags);
+            flags.set_carry(carry);
+            res
+        },
+        UnaryOp::Dec => {
+            let res = sub(width, dest, 1, false, flags);
+            flags.set_carry(carry);
+            res
+        },
+        UnaryOp::Not => logic(width, !dest as u32, flags),
+    }
+}
+
+fn sign_extend(value : u64, bits : u32) -> i64 {
+    ((value << (64 - bits)) as i64) >> (64 - bits)
+}
+
+/// Computes `dest op src` where `dest` and the result are twice as wide as `width`.
+/// Products set carry and overflow when the upper half is significant, divisions leave the flags untouched and
+/// return the quotient in the lower half and the remainder in the upper one
+pub fn mul(op : &MulOp, width : Width, dest : u32, src : u16, flags : &mut Flags) -> Result<u32> {
+    let (mask, _) = masks(width);
+    let bits = bits(width);
+    let (a, b) = (dest as u64 & mask as u64, src as u64 & mask as u64);
+    let dividend = dest as u64 & ((1 << (2 * bits)) - 1);
+
+    let res = match op {
+        MulOp::Mul => {
+            let res = a * b;
+            flags.set_carry(res >> bits != 0);
+            flags.set_overflow(res >> bits != 0);
+            res
+        },
+        MulOp::Imul => {
+            let res = sign_extend(a, bits) * sign_extend(b, bits);
+            let fits = res == sign_extend(res as u64 & mask as u64, bits);
+            flags.set_carry(!fits);
+            flags.set_overflow(!fits);
+            res as u64
+        },
+        MulOp::Div => {
+            if b == 0 { return Err(Error::DivisionByZero) }
+            let (quotient, remainder) = (dividend / b, dividend % b);
+            if quotient > mask as u64 { return Err(Error::DivisionOverflow(width)) }
+            quotient | (remainder << bits)
+        },
+        MulOp::Idiv => {
+            if b == 0 { return Err(Error::DivisionByZero) }
+            let (dividend, divisor) = (sign_extend(dividend, 2 * bits), sign_extend(b, bits));
+            let (quotient, remainder) = (dividend / divisor, dividend % divisor);
+            if quotient != sign_extend(quotient as u64 & mask as u64, bits) { return Err(Error::DivisionOverflow(width)) }
+            (quotient as u64 & mask as u64) | ((remainder as u64 & mask as u64) << bits)
+        },
+    };
+    Ok(res as u32)
+}
@@ -1,40 +1,189 @@
+use std::collections::HashMap;
+
 #[allow(unused_imports)]
 use common::prelude::*;
 
 mod utils;
 use utils::RegisterValue;
 
+mod alu;
+
+mod state;
+pub use state::{RunState, StopReason};
+
+mod port;
+pub use port::Port;
+
 #[cfg(test)]
 mod test;
 
 pub struct VM {
     regs : [RegisterValue; 16],
     rom : Vec<u8>,
+    display : Vec<u8>,
+    io : Vec<u8>,
     ram : Vec<u8>,
+    /// One bit per IRQ line waiting to be serviced
+    pending_irqs : u16,
+    state : RunState,
+    ports : HashMap<u8, Box<dyn Port>>,
 }
 
 impl VM {
-    pub fn new(rom : Vec<u8>, ram_size : usize) -> Self {
-        Self {
+    /// Handler addresses, one word per vector, at the top of the RAM. The stack grows towards them and stops right before
+    pub const VECTOR_TABLE : u16 = 0xFE00;
+    /// Vector of IRQ line 0, the ones below are left for software interrupts
+    pub const IRQ_VECTORS : u8 = 0x20;
+    #[allow(dead_code)]
+    pub const IRQ_LINES : u8 = 16;
+
+    pub const DISPLAY_START : u16 = 0x6000;
+    pub const IO_START : u16 = 0x7800;
+    pub const RAM_START : u16 = 0x8000;
+
+    /// The RAM must reach the end of the vector table, which is the end of the address space
+    pub fn new(rom : Vec<u8>, ram_size : usize) -> Result<Self> {
+        let table_end = Self::VECTOR_TABLE as usize + 2 * (u8::MAX as usize + 1);
+        if Self::RAM_START as usize + ram_size < table_end {
+            return Err(Error::Misc(format!("RAM of {ram_size:#06x} bytes doesn't cover the vector table at {:#06x}", Self::VECTOR_TABLE)));
+        }
+
+        Ok(Self {
             regs: [RegisterValue(0); 16],
             rom,
+            display: vec![0; (Self::IO_START - Self::DISPLAY_START) as usize],
+            io: vec![0; (Self::RAM_START - Self::IO_START) as usize],
             ram: vec![0; ram_size],
-        }
+            pending_irqs: 0,
+            state: RunState::Running,
+            ports: HashMap::new(),
+        })
     }
 
-    pub fn boot(&mut self) {
+    /// Resets execution to the start of the ROM, with an empty stack starting at `stack`
+    pub fn boot(&mut self, stack : u16) {
         self.set_reg_value(&Register::rip(), 0);
-        self.ram = vec![0; self.ram.len()];
+        self.set_reg_value(&Register::rsb(), stack);
+        self.set_reg_value(&Register::rsh(), stack);
+        self.display.fill(0);
+        self.io.fill(0);
+        self.ram.fill(0);
+        self.pending_irqs = 0;
+        self.state = RunState::Running;
+    }
+
+    /// Handles `in` and `out` on `port` from now on, replacing any previous handler
+    #[allow(dead_code)] // No devices are emulated yet
+    pub fn register_port(&mut self, port : u8, handler : impl Port + 'static) {
+        self.ports.insert(port, Box::new(handler));
+    }
+
+    fn port(&mut self, port : &Operand) -> Result<&mut Box<dyn Port>> {
+        let port = self.read(port, Width::Byte).get_byte(0);
+        self.ports.get_mut(&port).ok_or(Error::NoSuchPort(port))
+    }
+
+    /// Marks `line` as pending, it will be serviced before the next instruction once interrupts are enabled
+    #[allow(dead_code)] // No devices are emulated yet
+    pub fn raise_irq(&mut self, line : u8) {
+        assert!(line < Self::IRQ_LINES, "no such IRQ line {line}");
+        self.pending_irqs |= 1 << line;
+    }
+
+    /// Services the lowest pending IRQ line, if interrupts are enabled
+    fn service_irq(&mut self) -> Result<()> {
+        if !self.irq_ready() {
+            return Ok(());
+        }
+
+        let line = self.pending_irqs.trailing_zeros() as u8;
+        self.pending_irqs &= !(1 << line);
+        self.interrupt(Self::IRQ_VECTORS + line)
+    }
+
+    fn irq_ready(&self) -> bool {
+        self.pending_irqs != 0 && self.flags().interrupt_enable()
+    }
+
+    /// Saves Flags and RIP on the stack and jumps to the handler of `vector` with interrupts disabled
+    fn interrupt(&mut self, vector : u8) -> Result<()> {
+        self.push(&self.get_reg(&Register::flags()))?;
+        self.push(&self.get_reg(&Register::rip()))?;
+        self.set_interrupt_enable(false);
+
+        let handler = self.get_mem(Self::VECTOR_TABLE + 2 * vector as u16, Width::Word);
+        self.set_reg(&Register::rip(), &handler);
+        Ok(())
+    }
+
+    fn set_interrupt_enable(&mut self, value : bool) {
+        let mut flags = self.flags();
+        flags.set_interrupt_enable(value);
+        self.set_flags(flags)
     }
 
+    /// Executes instructions until the CPU stops by itself, gets stuck or `limit` instructions have been executed
+    pub fn run(&mut self, limit : Option<usize>) -> StopReason {
+        let mut executed = 0;
+        loop {
+            if limit.is_some_and(|limit| executed >= limit) {
+                return StopReason::Limit;
+            }
+
+            let rip = self.get_reg(&Register::rip()).get_word(0);
+            if let Err(err) = self.execute_next() {
+                return StopReason::Fault(err);
+            }
+            executed += 1;
+
+            match self.state {
+                RunState::Halted => return StopReason::Halted,
+                // Nothing raises IRQs while running
+                RunState::Waiting if self.pending_irqs == 0 => return StopReason::Waiting,
+                _ => (),
+            }
+
+            // The instruction moved RIP back to itself, it will keep doing so until interrupted
+            if self.get_reg(&Register::rip()).get_word(0) == rip && !self.irq_ready() {
+                return StopReason::SelfLoop(rip);
+            }
+        }
+    }
+
+    /// Does nothing while halted, or while waiting until an IRQ is raised, as stopping there is no error.
+    /// Once faulted, returns the same fault again without executing anything until booted again.
+    /// IRQs are only serviced here, so every instruction, read-modify-write ones included, completes before them
     pub fn execute_next(&mut self) -> Result<()> {
+        match &self.state {
+            RunState::Halted => return Ok(()),
+            RunState::Faulted(err) => return Err(err.clone()),
+            RunState::Waiting if self.pending_irqs == 0 => return Ok(()),
+            _ => self.state = RunState::Running,
+        }
+
+        let res = self.step();
+        if let Err(err) = &res {
+            self.state = RunState::Faulted(err.clone());
+        }
+        res
+    }
+
+    fn step(&mut self) -> Result<()> {
+        self.service_irq()?;
+
         let rip = self.get_reg(&Register::rip()).get_word(0);
 
-        let bytes : Vec<_> = (0..6).map(|offset| self.get_mem_byte(rip.wrapping_add(offset))).collect();
-        let instr = Instruction::decompile(&bytes)?;
+        // Fetch only as many bytes as the opcode says
+        let mut bytes = [0; Instruction::MAX_LEN as usize];
+        bytes[0] = self.get_mem_byte(rip);
+        let len = Instruction::len_of(bytes[0]).ok_or(Error::NoSuchOpcode(bytes[0]))?;
+        for offset in 1..len {
+            bytes[offset as usize] = self.get_mem_byte(rip.wrapping_add(offset));
+        }
+        let instr = Instruction::decompile(&bytes[..len as usize])?;
 
         // Move RIP
-        self.set_reg_value(&Register::rip(), rip.wrapping_add(instr.len()) as u16);
+        self.set_reg_value(&Register::rip(), rip.wrapping_add(len));
 
         self.execute(&instr)?;
 
@@ -45,43 +194,255 @@ impl VM {
         use Instruction::*;
         match instr {
             Nop => (),
-            MovI2R(value, dest) => self.set_reg(dest, value),
-            MovI2RP(value, dest) => self.set_mem(self.get_reg(dest).get_word(0), value),
-            MovI2IP(value, dest) => self.set_mem(dest.get_word(0), value),
-            MovIP2R(src, dest) => {
-                let value = self.get_mem(src.get_word(0), dest.width());
-                self.set_reg(dest, &value)
-            },
-            MovIP2RP(src, dest) => {
-                let value = self.get_mem(src.get_word(0), Width::Byte);
-                self.set_mem(self.get_reg(dest).get_word(0), &value)
-            },
-            MovIP2IP(src, dest) => {
-                let value = self.get_mem(src.get_word(0), Width::Byte);
-                let addr = self.get_mem(dest.get_word(0), dest.width()).get_word(0);
-                self.set_mem(addr, &value)
-            },
-            MovR2R(src, dest) => self.set_reg(dest, &self.get_reg(src)),
-            MovR2RP(src, dest) => self.set_mem(self.get_reg(dest).get_word(0), &self.get_reg(src)),
-            MovR2IP(src, dest) => self.set_mem(dest.get_word(0), &self.get_reg(src)),
-            MovRP2R(src, dest) => {
-                let value = self.get_mem(self.get_reg(src).get_word(0), dest.width());
-                self.set_reg(dest, &value)
-            },
-            MovRP2RP(src, dest) => {
-                let value = self.get_mem(self.get_reg(src).get_word(0), Width::Byte);
-                self.set_mem(self.get_reg(dest).get_word(0), &value)
-            },
-            MovRP2IP(src, dest) => {
-                let value = self.get_mem(self.get_reg(src).get_word(0), Width::Byte);
-                let addr = self.get_mem(dest.get_word(0), Width::Word).get_word(0);
-                dbg!(src, dest, value, addr);
-                self.set_mem(addr, &value)
+            Binary(op, src, dest) | BinaryMem(op, _, src, dest) => {
+                let width = instr.data_width().expect("Instruction::is_valid ensures a known width");
+                self.binary(op, width, src, dest)?
+            },
+            Unary(op, dest) | UnaryMem(op, _, dest) => {
+                let width = instr.data_width().expect("Instruction::is_valid ensures a known width");
+                let value = self.read(dest, width);
+                let value = self.unary(op, &value);
+                self.write(dest, &value)
+            },
+
+            Jmp(cond, target) => {
+                let target = self.read(target, Width::Word);
+                self.jump(cond, target.get_word(0))
+            },
+            Call(target) => {
+                let target = self.read(target, Width::Word);
+                self.call(target.get_word(0))?
             },
+            Ret => {
+                let rip = self.pop(Width::Word)?;
+                self.set_reg(&Register::rip(), &rip)
+            },
+
+            Push(src) => {
+                let value = self.read(src, Width::Word);
+                self.push(&value)?
+            },
+            Pop(dest) => {
+                let value = self.pop(dest.width().unwrap_or(Width::Word))?;
+                self.write(dest, &value)
+            },
+
+            Int(vector) => self.interrupt(vector.get_byte(0))?,
+            Iret => {
+                let rip = self.pop(Width::Word)?;
+                let flags = self.pop(Width::Word)?;
+                self.set_reg(&Register::rip(), &rip);
+                self.set_reg(&Register::flags(), &flags)
+            },
+            Sti => self.set_interrupt_enable(true),
+            Cli => self.set_interrupt_enable(false),
+            Hlt => self.state = RunState::Halted,
+            Wfi => self.state = RunState::Waiting,
+
+            In(port, dest) => {
+                let value = self.port(port)?.read(dest.width());
+                self.set_reg(dest, &Immediate::new_unchecked(dest.width(), value))
+            },
+            Out(src, port) => {
+                let value = self.get_reg(src);
+                self.port(port)?.write(value.width(), value.get_word(0))
+            },
+
+            Block(op, width, src, dest, count) => self.block(op, *width, src, dest, count),
         };
         Ok(())
     }
 
+    /// Memory operands are read with `width`
+    fn binary(&mut self, op : &BinaryOp, width : Width, src : &Operand, dest : &Operand) -> Result<()> {
+        match op {
+            BinaryOp::Mov => {
+                let value = self.read(src, width);
+                self.write(dest, &value)
+            },
+            BinaryOp::Movx(ext) => {
+                let Operand::Reg(dest) = dest else { unreachable!("Instruction::is_valid ensures a register destination") };
+                let value = self.read(src, Width::Byte).get_byte(0);
+                self.set_reg_value(dest, ext.extend(value))
+            },
+            BinaryOp::Xchg => {
+                let (value, current) = (self.read(src, width), self.read(dest, width));
+                self.write(dest, &value);
+                self.write(src, &current)
+            },
+            BinaryOp::Cmpxchg => {
+                let (value, current) = (self.read(src, width), self.read(dest, width));
+                if let Some(value) = self.cmpxchg(&value, &current) {
+                    self.write(dest, &value)
+                }
+            },
+            BinaryOp::Alu(op) => {
+                let (value, current) = (self.read(src, width), self.read(dest, width));
+                let value = self.alu(op, &current, &value);
+                if op.stores_result() {
+                    self.write(dest, &value)
+                }
+            },
+            BinaryOp::Mul(op) => {
+                let Operand::Reg(dest) = dest else { unreachable!("Instruction::is_valid ensures a register destination") };
+                let value = self.read(src, width);
+                self.mul(op, dest, &value)?
+            },
+        };
+        Ok(())
+    }
+
+    /// Address of a memory operand
+    fn address(&self, operand : &Operand) -> u16 {
+        operand.effective_address(|reg| self.get_reg(reg).get_word(0))
+            .unwrap_or_else(|| unreachable!("{operand:?} is not in memory"))
+    }
+
+    /// Memory operands are read with `width`, the others have their own
+    fn read(&mut self, operand : &Operand, width : Width) -> Immediate {
+        match operand {
+            Operand::Imm(value) => *value,
+            Operand::Reg(reg) => self.get_reg(reg),
+            Operand::Rel(disp) => Immediate::word(self.get_reg(&Register::rip()).get_word(0).wrapping_add(disp.get_word(0))),
+            Operand::ImmPtr(_) | Operand::RegPtr(_) | Operand::Indexed(_) => self.get_mem(self.address(operand), width),
+        }
+    }
+
+    fn write(&mut self, operand : &Operand, value : &Immediate) {
+        match operand {
+            Operand::Reg(reg) => self.set_reg(reg, value),
+            Operand::ImmPtr(_) | Operand::RegPtr(_) | Operand::Indexed(_) => self.set_mem(self.address(operand), value),
+            Operand::Imm(_) | Operand::Rel(_) => unreachable!("Instruction::is_valid ensures {operand:?} is not written to"),
+        }
+    }
+
+    fn alu(&mut self, op : &AluOp, dest : &Immediate, src : &Immediate) -> Immediate {
+        let mut flags = self.flags();
+        let value = alu::alu(op, src.width(), dest.get_word(0), src.get_word(0), &mut flags);
+        self.set_flags(flags);
+        Immediate::new_unchecked(src.width(), value)
+    }
+
+    fn unary(&mut self, op : &UnaryOp, dest : &Immediate) -> Immediate {
+        let mut flags = self.flags();
+        let value = alu::unary(op, dest.width(), dest.get_word(0), &mut flags);
+        self.set_flags(flags);
+        Immediate::new_unchecked(dest.width(), value)
+    }
+
+    /// Byte results take the whole word register of `dest`, word results span `dest` and the register after it
+    fn mul(&mut self, op : &MulOp, dest : &Register, src : &Immediate) -> Result<()> {
+        let low = dest.with_width(Width::Word);
+        let high = match dest.width() {
+            Width::Byte => None,
+            Width::Word => Some(dest.next().expect("Instruction::is_valid ensures a register pair")),
+        };
+        let high_value = high.map_or(0, |high| self.get_reg(&high).get_word(0));
+        let dest_value = self.get_reg(&low).get_word(0) as u32 | (high_value as u32) << 16;
+
+        let mut flags = self.flags();
+        let value = alu::mul(op, dest.width(), dest_value, src.get_word(0), &mut flags)?;
+        self.set_flags(flags);
+
+        self.set_reg_value(&low, value as u16);
+        if let Some(high) = high {
+            self.set_reg_value(&high, (value >> 16) as u16);
+        }
+        Ok(())
+    }
+
+    /// Compares the accumulator (`r0` with the width of `src`) with `current` like `cmp current, r0`.
+    /// Returns `src` to store back if they are equal, loads `current` into the accumulator otherwise
+    fn cmpxchg(&mut self, src : &Immediate, current : &Immediate) -> Option<Immediate> {
+        let acc = Register::r0().with_width(src.width());
+        self.alu(&AluOp::Cmp, &self.get_reg(&acc), current);
+        if self.flags().zero() {
+            Some(*src)
+        } else {
+            self.set_reg(&acc, current);
+            None
+        }
+    }
+
+    /// Goes forwards through the blocks one element at a time, so they can span several memory regions.
+    /// Leaves the pointers after the last element processed and the count with the elements left
+    fn block(&mut self, op : &BlockOp, width : Width, src : &Register, dest : &Register, count : &Register) {
+        let len = width.len() as u16;
+        while self.get_reg(count).get_word(0) != 0 {
+            let src_addr = self.get_reg(src).get_word(0);
+            let dest_addr = self.get_reg(dest).get_word(0);
+
+            let done = match op {
+                BlockOp::Copy => {
+                    let value = self.get_mem(src_addr, width);
+                    self.set_mem(dest_addr, &value);
+                    false
+                },
+                BlockOp::Fill => {
+                    self.set_mem(dest_addr, &self.get_reg(src));
+                    false
+                },
+                BlockOp::Compare => {
+                    let (value, other) = (self.get_mem(src_addr, width), self.get_mem(dest_addr, width));
+                    self.alu(&AluOp::Cmp, &other, &value);
+                    !self.flags().zero()
+                },
+                BlockOp::Scan => {
+                    let other = self.get_mem(dest_addr, width);
+                    self.alu(&AluOp::Cmp, &other, &self.get_reg(src));
+                    self.flags().zero()
+                },
+            };
+
+            if op.has_src() {
+                self.set_reg_value(src, src_addr.wrapping_add(len));
+            }
+            self.set_reg_value(dest, dest_addr.wrapping_add(len));
+            self.set_reg_value(count, self.get_reg(count).get_word(0) - 1);
+
+            if done {
+                break;
+            }
+        }
+    }
+
+    fn jump(&mut self, cond : &Condition, addr : u16) {
+        if cond.holds(self.flags()) {
+            self.set_reg_value(&Register::rip(), addr)
+        }
+    }
+
+    /// Pushes the return address (the already advanced RIP) before jumping
+    fn call(&mut self, addr : u16) -> Result<()> {
+        self.push(&self.get_reg(&Register::rip()))?;
+        self.set_reg_value(&Register::rip(), addr);
+        Ok(())
+    }
+
+    /// The stack grows upwards from RSB up to the vector table, RSH points to the first free byte
+    pub fn push(&mut self, value : &Immediate) -> Result<()> {
+        let head = self.get_reg(&Register::rsh()).get_word(0);
+        let new_head = head as u32 + value.width().len() as u32;
+        if new_head > Self::VECTOR_TABLE as u32 {
+            return Err(Error::StackOverflow(head));
+        }
+
+        self.set_mem(head, value);
+        self.set_reg_value(&Register::rsh(), new_head as u16);
+        Ok(())
+    }
+
+    pub fn pop(&mut self, width : Width) -> Result<Immediate> {
+        let head = self.get_reg(&Register::rsh()).get_word(0);
+        let base = self.get_reg(&Register::rsb()).get_word(0);
+        let Some(new_head) = head.checked_sub(width.len() as u16).filter(|new_head| *new_head >= base) else {
+            return Err(Error::StackUnderflow(head));
+        };
+
+        self.set_reg_value(&Register::rsh(), new_head);
+        Ok(self.get_mem(new_head, width))
+    }
+
     pub fn set_reg(&mut self, reg : &Register, value : &Immediate) {
         let reg = &mut self.regs[reg.as_src() as usize];
         match value.width() {
@@ -103,25 +464,24 @@ impl VM {
         match value.width() {
             Width::Byte => self.set_mem_byte(addr, value.get_byte(0)),
             Width::Word => for offset in 0..=1 {
-                self.set_mem_byte(addr + offset, value.get_byte(offset as u8));
+                self.set_mem_byte(addr.wrapping_add(offset), value.get_byte(offset as u8));
             },
         }
     }
 
     pub fn set_mem_byte(&mut self, addr : u16, value : u8) {
-        if addr < 0x6000 {
-            if let Some(b) = self.rom.get_mut(addr as usize) {
-                *b = value;
-            }
-        } else if addr < 0x7800 {
-            todo!("Display")
-        } else if addr < 0x8000 {
-            todo!("IO")
+        let (region, offset) = if addr < Self::DISPLAY_START {
+            (&mut self.rom, addr)
+        } else if addr < Self::IO_START {
+            (&mut self.display, addr - Self::DISPLAY_START)
+        } else if addr < Self::RAM_START {
+            (&mut self.io, addr - Self::IO_START)
         } else {
-            #[allow(clippy::collapsible_else_if)]
-            if let Some(b) = self.ram.get_mut((addr - 0x8000) as usize) {
-                *b = value;
-            }
+            (&mut self.ram, addr - Self::RAM_START)
+        };
+
+        if let Some(b) = region.get_mut(offset as usize) {
+            *b = value;
         }
     }
 
@@ -135,15 +495,25 @@ impl VM {
     }
 
     pub fn get_mem_byte(&self, addr : u16) -> u8 {
-        if addr < 0x6000 {
-            self.rom.get(addr as usize).map_or(0, |b| *b)
-        } else if addr < 0x7800 {
-            todo!("Display")
-        } else if addr < 0x8000 {
-            todo!("IO")
+        let (region, offset) = if addr < Self::DISPLAY_START {
+            (&self.rom, addr)
+        } else if addr < Self::IO_START {
+            (&self.display, addr - Self::DISPLAY_START)
+        } else if addr < Self::RAM_START {
+            (&self.io, addr - Self::IO_START)
         } else {
-            self.ram.get((addr - 0x8000) as usize).map_or(0, |b| *b)
-        }
+            (&self.ram, addr - Self::RAM_START)
+        };
+
+        region.get(offset as usize).map_or(0, |b| *b)
+    }
+
+    pub fn flags(&self) -> Flags {
+        self.get_reg(&Register::flags()).get_word(0).into()
+    }
+
+    pub fn set_flags(&mut self, flags : Flags) {
+        self.set_reg_value(&Register::flags(), flags.into())
     }
 
     pub fn regs(&self) -> Vec<u16> {
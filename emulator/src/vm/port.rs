@@ -0,0 +1,8 @@
+#[allow(unused_imports)]
+use common::prelude::*;
+
+/// A device register in the port address space, `in` and `out` read and write it with the width of their register
+pub trait Port {
+    fn read(&mut self, width : Width) -> u16;
+    fn write(&mut self, width : Width, value : u16);
+}
@@ -0,0 +1,40 @@
+#[allow(unused_imports)]
+use common::prelude::*;
+
+/// What the CPU does when asked to execute the next instruction
+#[derive(Debug, Clone, PartialEq)]
+pub enum RunState {
+    Running,
+    /// Stopped by `hlt`, only booting again resumes execution
+    Halted,
+    /// Stopped by `wfi` until an IRQ is raised
+    Waiting,
+    /// Stopped by an instruction that failed, only booting again resumes execution
+    Faulted(Error),
+}
+
+/// Why `VM::run` returned
+#[derive(Debug, Clone, PartialEq)]
+pub enum StopReason {
+    Halted,
+    /// Waiting for an IRQ that nothing is going to raise
+    Waiting,
+    Fault(Error),
+    /// Executed as many instructions as allowed
+    Limit,
+    /// The instruction at this address jumps to itself and no IRQ can interrupt it
+    SelfLoop(u16),
+}
+
+impl std::fmt::Display for StopReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        use StopReason::*;
+        match self {
+            Halted => write!(f, "halted"),
+            Waiting => write!(f, "waiting for an interrupt"),
+            Fault(err) => write!(f, "fault: {err}"),
+            Limit => write!(f, "reached the instruction limit"),
+            SelfLoop(addr) => write!(f, "infinite loop at {addr:#06x}"),
+        }
+    }
+}
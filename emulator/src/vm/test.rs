@@ -8,9 +8,9 @@ macro_rules! case {
         #[test]
         fn $ident() {
             let rom = $code.into_iter().flat_map(|instr| instr.unwrap().compile()).collect();
-            let mut vm = VM::new(rom, 0x8000);
+            let mut vm = VM::new(rom, 0x8000).unwrap();
 
-            vm.boot();
+            vm.boot(0x8000);
             for _ in 0..$reps {
                 vm.execute_next().unwrap();
             }
@@ -27,11 +27,11 @@ macro_rules! case {
     };
 }
 
-case!(nop, [Instruction::nop()], 1, [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0x02, 0]);
+case!(nop, [Instruction::nop()], 1, [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0x8000, 0x8000, 0, 0x02, 0]);
 case!(movi2r, [
     Instruction::movi2r(Immediate::byte(0x60), Register::rb0()),
     Instruction::movi2r(Immediate::word(0x600D), Register::r1()),
-], 2, [0x60, 0x600D, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0x08, 0]);
+], 2, [0x60, 0x600D, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0x8000, 0x8000, 0, 0x08, 0]);
 case!(
     movi2rp,
     [
@@ -41,7 +41,7 @@ case!(
         Instruction::movi2rp(Immediate::word(0x600D), Register::r1()),
     ],
     4,
-    [0x8000, 0x8002, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0x10, 0],
+    [0x8000, 0x8002, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0x8000, 0x8000, 0, 0x10, 0],
     [(0x8000, 0x60), (0x8001, 0x00), (0x8002, 0x0D), (0x8003, 0x60)]
 );
 case!(
@@ -51,32 +51,33 @@ case!(
         Instruction::movi2ip(Immediate::word(0x600D), Immediate::word(0xF335)),
     ],
     2,
-    [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0x0C, 0],
+    [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0x8000, 0x8000, 0, 0x0C, 0],
     [(0xF335, 0x0D), (0xF336, 0x60), (0xF337, 0x60)]
 );
 case!(movip2r, [
     Instruction::movi2ip(Immediate::word(0x600D), Immediate::word(0xF337)),
     Instruction::movip2r(Immediate::word(0xF337), Register::rb0()),
     Instruction::movip2r(Immediate::word(0xF337), Register::r1()),
-], 3, [0x0D, 0x600D, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0xE, 0]);
+], 3, [0x0D, 0x600D, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0x8000, 0x8000, 0, 0xE, 0]);
 case!(
     movip2rp, [
         Instruction::movi2ip(Immediate::word(0x600D), Immediate::word(0xF337)),
         Instruction::movi2r(Immediate::word(0xF338), Register::r0()),
-        Instruction::movip2rp(Immediate::word(0xF337), Register::r0()),
+        Instruction::movip2rp(Width::Word, Immediate::word(0xF337), Register::r0()),
     ],
     3,
-    [0xF338, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0xE, 0],
-    [(0xF337, 0x0D), (0xF338, 0x0D)]
+    [0xF338, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0x8000, 0x8000, 0, 0xE, 0],
+    [(0xF337, 0x0D), (0xF338, 0x0D), (0xF339, 0x60)]
 );
 case!(
     movip2ip, [
         Instruction::movi2ip(Immediate::word(0x600D), Immediate::word(0xF337)),
-        Instruction::movip2ip(Immediate::word(0xF337), Immediate::word(0xF338)),
+        Instruction::movip2ip(Width::Byte, Immediate::word(0xF337), Immediate::word(0xF339)),
+        Instruction::movip2ip(Width::Word, Immediate::word(0xF337), Immediate::word(0xF33A)),
     ],
-    2,
-    [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0xC, 0],
-    [(0xF337, 0x0D), (0xF338, 0x60)]
+    3,
+    [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0x8000, 0x8000, 0, 0x12, 0],
+    [(0xF337, 0x0D), (0xF338, 0x60), (0xF339, 0x0D), (0xF33A, 0x0D), (0xF33B, 0x60)]
 );
 case!(movr2r, [
     Instruction::movi2r(Immediate::byte(0x60), Register::rb0()),
@@ -87,7 +88,7 @@ case!(movr2r, [
     Instruction::movr2r(Register::r1(), Register::r1()),
     Instruction::movr2r(Register::rb0(), Register::rb2()),
     Instruction::movr2r(Register::r1(), Register::r3()),
-], 8, [0x60, 0x600D, 0xF360, 0x600D, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0x18, 0]);
+], 8, [0x60, 0x600D, 0xF360, 0x600D, 0, 0, 0, 0, 0, 0, 0, 0x8000, 0x8000, 0, 0x18, 0]);
 case!(
     movr2rp, [
         Instruction::movi2r(Immediate::word(0x600D), Register::r0()),
@@ -97,7 +98,7 @@ case!(
         Instruction::movr2rp(Register::r0(), Register::r2()),
     ],
     5,
-    [0x600D, 0xF337, 0xF338, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0x10, 0],
+    [0x600D, 0xF337, 0xF338, 0, 0, 0, 0, 0, 0, 0, 0, 0x8000, 0x8000, 0, 0x10, 0],
     [(0xF337, 0x0D), (0xF338, 0x0D), (0xF339, 0x60)]
 );
 case!(
@@ -107,7 +108,7 @@ case!(
         Instruction::movr2ip(Register::r0(), Immediate::word(0xF338)),
     ],
     3,
-    [0x600D, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0xC, 0],
+    [0x600D, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0x8000, 0x8000, 0, 0xC, 0],
     [(0xF337, 0x0D), (0xF338, 0x0D), (0xF339, 0x60)]
 );
 case!(
@@ -119,7 +120,7 @@ case!(
         Instruction::movrp2r(Register::r0(), Register::r2()),
     ],
     4,
-    [0xF337, 0x0D, 0x600D, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0xE, 0],
+    [0xF337, 0x0D, 0x600D, 0, 0, 0, 0, 0, 0, 0, 0, 0x8000, 0x8000, 0, 0xE, 0],
     [(0xF337, 0x0D), (0xF338, 0x60)]
 );
 case!(
@@ -128,10 +129,10 @@ case!(
         Instruction::movi2r(Immediate::word(0xF337), Register::r0()),
         Instruction::movi2r(Immediate::word(0xF339), Register::r1()),
         Instruction::movi2ip(Immediate::word(0x600D), Immediate::word(0xF337)),
-        Instruction::movrp2rp(Register::r0(), Register::r1()),
+        Instruction::movrp2rp(Width::Byte, Register::r0(), Register::r1()),
     ],
     4,
-    [0xF337, 0xF339, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0x10, 0],
+    [0xF337, 0xF339, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0x8000, 0x8000, 0, 0x10, 0],
     [(0xF337, 0x0D), (0xF338, 0x60), (0xF339, 0x0D), (0xF33A, 0)]
 );
 case!(
@@ -139,9 +140,720 @@ case!(
     [
         Instruction::movi2r(Immediate::word(0xF337), Register::r0()),
         Instruction::movi2ip(Immediate::word(0xF339), Immediate::word(0xF337)),
-        Instruction::movrp2ip(Register::r0(), Immediate::word(0xF337)),
+        Instruction::movrp2ip(Width::Word, Register::r0(), Immediate::word(0xF339)),
+    ],
+    3,
+    [0xF337, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0x8000, 0x8000, 0, 0x0E, 0],
+    [(0xF337, 0x39), (0xF338, 0xF3), (0xF339, 0x39), (0xF33A, 0xF3)]
+);
+
+case!(add, [
+    Instruction::movi2r(Immediate::byte(0xF3), Register::rb0()),
+    Instruction::movi2r(Immediate::byte(0x0D), Register::rb1()),
+    Instruction::alur2r(AluOp::Add, Register::rb0(), Register::rb1()),
+], 3, [0xF3, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0x8000, 0x8000, (Flags::ZERO | Flags::CARRY).bits(), 0x0B, 0]);
+case!(adc, [
+    Instruction::movi2r(Immediate::word(0xFFFF), Register::r0()),
+    Instruction::alui2r(AluOp::Add, Immediate::word(0x0001), Register::r0()),
+    Instruction::movi2r(Immediate::word(0x1234), Register::r1()),
+    Instruction::alui2r(AluOp::Adc, Immediate::word(0x0001), Register::r1()),
+], 4, [0, 0x1236, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0x8000, 0x8000, 0, 0x12, 0]);
+case!(sub, [
+    Instruction::movi2r(Immediate::word(0x0CF3), Register::r0()),
+    Instruction::movi2r(Immediate::word(0x000D), Register::r2()),
+    Instruction::alur2r(AluOp::Sub, Register::r0(), Register::r2()),
+], 3, [0x0CF3, 0, 0xF31A, 0, 0, 0, 0, 0, 0, 0, 0, 0x8000, 0x8000, (Flags::CARRY | Flags::SIGN).bits(), 0x0B, 0]);
+case!(sbb, [
+    Instruction::alui2r(AluOp::Sub, Immediate::word(0x0001), Register::r0()),
+    Instruction::movi2r(Immediate::word(0x8000), Register::r1()),
+    Instruction::alui2r(AluOp::Sbb, Immediate::word(0x0000), Register::r1()),
+], 3, [0xFFFF, 0x7FFF, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0x8000, 0x8000, Flags::OVERFLOW.bits(), 0x0E, 0]);
+case!(
+    alu_mem, [
+        Instruction::movi2ip(Immediate::word(0x00FF), Immediate::word(0x8000)),
+        Instruction::alui2ip(AluOp::Add, Immediate::word(0x0001), Immediate::word(0x8000)),
+        Instruction::movi2r(Immediate::word(0x8000), Register::r0()),
+        Instruction::movi2r(Immediate::byte(0x01), Register::rb1()),
+        Instruction::alur2rp(AluOp::Sub, Register::rb1(), Register::r0()),
+    ],
+    5,
+    [0x8000, 0x01, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0x8000, 0x8000, (Flags::CARRY | Flags::SIGN).bits(), 0x18, 0],
+    [(0x8000, 0xFF), (0x8001, 0x01)]
+);
+case!(
+    alu_mem2mem, [
+        Instruction::movi2ip(Immediate::word(0x00FF), Immediate::word(0x8000)),
+        Instruction::movi2ip(Immediate::word(0x0001), Immediate::word(0x8002)),
+        Instruction::aluip2ip(AluOp::Add, Width::Word, Immediate::word(0x8002), Immediate::word(0x8000)),
+        Instruction::movi2r(Immediate::word(0x8002), Register::r0()),
+        Instruction::alurp2ip(AluOp::Add, Width::Byte, Register::r0(), Immediate::word(0x8001)),
+    ],
+    5,
+    [0x8002, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0x8000, 0x8000, 0, 0x1C, 0],
+    [(0x8000, 0x00), (0x8001, 0x02), (0x8002, 0x01), (0x8003, 0x00)]
+);
+case!(
+    inc_dec, [
+        Instruction::movi2r(Immediate::word(0x7FFF), Register::r0()),
+        Instruction::movi2r(Immediate::word(Flags::CARRY.bits()), Register::flags()),
+        Instruction::unaryr(UnaryOp::Inc, Register::r0()),
+        Instruction::unaryip(UnaryOp::Dec, Width::Byte, Immediate::word(0x8000)),
+    ],
+    4,
+    [0x8000, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0x8000, 0x8000, (Flags::CARRY | Flags::SIGN).bits(), 0x10, 0],
+    [(0x8000, 0xFF)]
+);
+case!(logic, [
+    Instruction::movi2r(Immediate::word(0xF0F0), Register::r0()),
+    Instruction::alui2r(AluOp::And, Immediate::word(0x0FF0), Register::r0()),
+    Instruction::alui2r(AluOp::Or, Immediate::word(0x000F), Register::r0()),
+    Instruction::movi2r(Immediate::byte(0xFF), Register::rb1()),
+    Instruction::alur2r(AluOp::Xor, Register::rb1(), Register::rb1()),
+    Instruction::unaryr(UnaryOp::Not, Register::r0()),
+], 6, [0xFF00, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0x8000, 0x8000, Flags::SIGN.bits(), 0x18, 0]);
+case!(shift, [
+    Instruction::movi2r(Immediate::byte(0x81), Register::rb0()),
+    Instruction::alui2r(AluOp::Shl, Immediate::byte(0x01), Register::rb0()),
+    Instruction::movi2r(Immediate::word(0x8001), Register::r1()),
+    Instruction::alui2r(AluOp::Shr, Immediate::word(0x0001), Register::r1()),
+    Instruction::movi2r(Immediate::word(0x8000), Register::r2()),
+    Instruction::alui2r(AluOp::Sar, Immediate::word(0x0004), Register::r2()),
+], 6, [0x02, 0x4000, 0xF800, 0, 0, 0, 0, 0, 0, 0, 0, 0x8000, 0x8000, Flags::SIGN.bits(), 0x1B, 0]);
+case!(rotate, [
+    Instruction::movi2r(Immediate::byte(0x81), Register::rb0()),
+    Instruction::alui2r(AluOp::Rol, Immediate::byte(0x01), Register::rb0()),
+    Instruction::movi2r(Immediate::word(0x0001), Register::r1()),
+    Instruction::movi2r(Immediate::word(0x0004), Register::r2()),
+    Instruction::alur2r(AluOp::Ror, Register::r2(), Register::r1()),
+], 5, [0x03, 0x1000, 0x0004, 0, 0, 0, 0, 0, 0, 0, 0, 0x8000, 0x8000, 0, 0x14, 0]);
+case!(rotate_wide, [
+    Instruction::movi2r(Immediate::word(0x0001), Register::r0()),
+    Instruction::alui2r(AluOp::Rol, Immediate::word(20), Register::r0()),
+    Instruction::movi2r(Immediate::byte(0x01), Register::rb1()),
+    Instruction::alui2r(AluOp::Rol, Immediate::byte(10), Register::rb1()),
+    Instruction::movi2r(Immediate::word(0x0001), Register::r2()),
+    Instruction::alui2r(AluOp::Ror, Immediate::word(17), Register::r2()),
+], 6, [0x0010, 0x04, 0x8000, 0, 0, 0, 0, 0, 0, 0, 0, 0x8000, 0x8000, 0x0E, 0x1B, 0]);
+case!(
+    logic_mem, [
+        Instruction::movi2ip(Immediate::word(0x1234), Immediate::word(0x8000)),
+        Instruction::alui2ip(AluOp::And, Immediate::word(0x00FF), Immediate::word(0x8000)),
+        Instruction::movi2r(Immediate::word(0x8001), Register::r0()),
+        Instruction::unaryrp(UnaryOp::Not, Width::Word, Register::r0()),
+    ],
+    4,
+    [0x8001, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0x8000, 0x8000, Flags::SIGN.bits(), 0x14, 0],
+    [(0x8000, 0x34), (0x8001, 0xFF), (0x8002, 0xFF)]
+);
+case!(cmp_test, [
+    Instruction::movi2r(Immediate::word(0x0005), Register::r0()),
+    Instruction::alui2r(AluOp::Cmp, Immediate::word(0x0006), Register::r0()),
+    Instruction::alui2r(AluOp::Test, Immediate::byte(0xF0), Register::rb0()),
+], 3, [0x0005, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0x8000, 0x8000, Flags::ZERO.bits(), 0x0E, 0]);
+
+#[test]
+fn flags_by_name() {
+    let code = [
+        Instruction::movi2r(Immediate::word(0x0005), Register::r0()),
+        Instruction::alui2r(AluOp::Cmp, Immediate::word(0x0005), Register::r0()),
+        Instruction::alui2r(AluOp::Cmp, Immediate::word(0x0006), Register::r0()),
+        Instruction::alur2r(AluOp::Test, Register::r0(), Register::r0()),
+    ];
+    let rom = code.into_iter().flat_map(|instr| instr.unwrap().compile()).collect();
+    let mut vm = VM::new(rom, 0x8000).unwrap();
+    vm.boot(0x8000);
+
+    vm.execute_next().unwrap();
+    vm.execute_next().unwrap();
+    assert!(vm.flags().zero() && !vm.flags().carry());
+
+    vm.execute_next().unwrap();
+    assert!(!vm.flags().zero() && vm.flags().carry() && vm.flags().sign() && !vm.flags().overflow());
+
+    vm.execute_next().unwrap();
+    assert_eq!(vm.flags(), Flags::empty());
+    assert_eq!(vm.get_reg(&Register::r0()).get_word(0), 0x0005);
+}
+
+case!(jmp, [
+    Instruction::movi2r(Immediate::word(0x000B), Register::r5()),
+    Instruction::jmpr(Condition::Always, Register::r5()),
+    Instruction::movi2r(Immediate::word(0x0BAD), Register::r0()),
+    Instruction::movi2r(Immediate::word(0x600D), Register::r1()),
+], 3, [0, 0x600D, 0, 0, 0, 0x000B, 0, 0, 0, 0, 0, 0x8000, 0x8000, 0, 0x0F, 0]);
+case!(
+    jmp_indirect, [
+        Instruction::movi2ip(Immediate::word(0x0013), Immediate::word(0x8000)),
+        Instruction::jmpip(Condition::Always, Immediate::word(0x8000)),
+        Instruction::movi2r(Immediate::word(0x0BAD), Register::r0()),
+        Instruction::movi2r(Immediate::word(0x0BAD), Register::r0()),
+        Instruction::movi2r(Immediate::word(0x8000), Register::r1()),
+        Instruction::movi2ip(Immediate::word(0x0024), Immediate::word(0x8000)),
+        Instruction::jmprp(Condition::Always, Register::r1()),
+        Instruction::movi2r(Immediate::word(0x0BAD), Register::r0()),
+    ],
+    5,
+    [0, 0x8000, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0x8000, 0x8000, 0, 0x24, 0]
+);
+// Counts r0 down from 3, adding it to r1 on each iteration
+case!(jnz_loop, [
+    Instruction::movi2r(Immediate::word(0x0003), Register::r0()),
+    Instruction::alur2r(AluOp::Add, Register::r0(), Register::r1()),
+    Instruction::unaryr(UnaryOp::Dec, Register::r0()),
+    Instruction::jmpi(Condition::NotZero, Immediate::word(0x0004)),
+], 10, [0, 6, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0x8000, 0x8000, Flags::ZERO.bits(), 0x0F, 0]);
+
+#[test]
+fn jcc() {
+    use Condition::*;
+    let all = [
+        Always, Zero, NotZero, Carry, NotCarry, Sign, NotSign, Overflow, NotOverflow,
+        Above, BelowEqual, Greater, GreaterEqual, Less, LessEqual,
+    ];
+    // (dest, src, conditions that hold after `cmp src, dest`)
+    let cases = [
+        (0x0005, 0x0005, vec![Always, Zero, NotCarry, NotSign, NotOverflow, BelowEqual, GreaterEqual, LessEqual]),
+        (0x0005, 0x0006, vec![Always, NotZero, Carry, Sign, NotOverflow, BelowEqual, Less, LessEqual]),
+        (0xFFFF, 0x0001, vec![Always, NotZero, NotCarry, Sign, NotOverflow, Above, Less, LessEqual]),
+        (0x8000, 0x0001, vec![Always, NotZero, NotCarry, NotSign, Overflow, Above, Less, LessEqual]),
+        (0x0001, 0xFFFF, vec![Always, NotZero, Carry, NotSign, NotOverflow, BelowEqual, Greater, GreaterEqual]),
+    ];
+
+    for (dest, src, taken) in cases {
+        for cond in all {
+            let code = [
+                Instruction::movi2r(Immediate::word(dest), Register::r0()),
+                Instruction::alui2r(AluOp::Cmp, Immediate::word(src), Register::r0()),
+                Instruction::jmpi(cond, Immediate::word(0x600D)),
+            ];
+            let rom = code.into_iter().flat_map(|instr| instr.unwrap().compile()).collect();
+            let mut vm = VM::new(rom, 0x8000).unwrap();
+            vm.boot(0x8000);
+            for _ in 0..3 {
+                vm.execute_next().unwrap();
+            }
+
+            let rip = vm.get_reg(&Register::rip()).get_word(0);
+            assert_eq!(rip == 0x600D, taken.contains(&cond), "cmp {src:#06X}, {dest:#06X}; {cond:?}");
+        }
+    }
+}
+
+case!(
+    push_pop, [
+        Instruction::movi2r(Immediate::word(0x600D), Register::r0()),
+        Instruction::pushr(Register::r0()),
+        Instruction::pushi(Immediate::byte(0x37)),
+        Instruction::pushi(Immediate::word(0xF337)),
+        Instruction::popr(Register::r1()),
+        Instruction::popr(Register::rb2()),
+        Instruction::popr(Register::r3()),
+    ],
+    7,
+    [0x600D, 0xF337, 0x37, 0x600D, 0, 0, 0, 0, 0, 0, 0, 0x8000, 0x8000, 0, 0x14, 0],
+    [(0x8000, 0x0D), (0x8001, 0x60), (0x8002, 0x37), (0x8003, 0x37), (0x8004, 0xF3)]
+);
+
+#[test]
+fn stack_bounds() {
+    let code = [
+        Instruction::pushi(Immediate::word(0x600D)),
+        Instruction::pushi(Immediate::byte(0x60)),
+        Instruction::popr(Register::r0()),
+        Instruction::popr(Register::rb0()),
+    ];
+    let rom = code.into_iter().flat_map(|instr| instr.unwrap().compile()).collect();
+    let mut vm = VM::new(rom, 0x8000).unwrap();
+    let stack = VM::VECTOR_TABLE - 2;
+    vm.boot(stack);
+
+    // Faults stop the CPU, pick up after each one to check the next
+    assert_eq!(vm.execute_next(), Ok(()));
+    assert_eq!(vm.execute_next(), Err(Error::StackOverflow(VM::VECTOR_TABLE)));
+    vm.state = RunState::Running;
+    assert_eq!(vm.execute_next(), Ok(()));
+    assert_eq!(vm.get_reg(&Register::r0()).get_word(0), 0x600D);
+    assert_eq!(vm.execute_next(), Err(Error::StackUnderflow(stack)));
+    assert_eq!(vm.get_reg(&Register::rsh()).get_word(0), stack);
+}
+
+// The stack stops before the vector table, so it can't overwrite the handlers
+#[test]
+fn stack_vector_table() {
+    let rom = Instruction::pushi(Immediate::word(0x600D)).unwrap().compile();
+    let mut vm = VM::new(rom, 0x8000).unwrap();
+    vm.boot(VM::VECTOR_TABLE - 1);
+    vm.set_mem(VM::VECTOR_TABLE, &Immediate::word(0xF337));
+
+    assert_eq!(vm.execute_next(), Err(Error::StackOverflow(VM::VECTOR_TABLE - 1)));
+    assert_eq!(vm.get_mem(VM::VECTOR_TABLE, Width::Word), Immediate::word(0xF337));
+
+    assert!(VM::new(vec![], 0x7E00).is_err());
+    assert!(VM::new(vec![], 0x7FFF).is_err());
+}
+
+// Calls a function that doubles r0 twice, through different kinds of targets
+case!(
+    call_ret, [
+        Instruction::movi2r(Immediate::word(0x0003), Register::r0()),
+        Instruction::calli(Immediate::word(0x0013)),
+        Instruction::movi2r(Immediate::word(0x0013), Register::r5()),
+        Instruction::callr(Register::r5()),
+        Instruction::jmpi(Condition::Always, Immediate::word(0x0018)),
+        Instruction::alur2r(AluOp::Add, Register::r0(), Register::r0()),
+        Instruction::ret(),
+    ],
+    9,
+    [0x000C, 0, 0, 0, 0, 0x0013, 0, 0, 0, 0, 0, 0x8000, 0x8000, 0, 0x18, 0],
+    [(0x8000, 0x0E), (0x8001, 0x00)]
+);
+
+// A call through memory that runs out of stack
+#[test]
+fn call_overflow() {
+    let rom = [
+        Instruction::movi2r(Immediate::word(0x8000), Register::r0()),
+        Instruction::callrp(Register::r0()),
+    ].into_iter().flat_map(|instr| instr.unwrap().compile()).collect();
+    let mut vm = VM::new(rom, 0x8000).unwrap();
+
+    vm.boot(VM::VECTOR_TABLE - 1);
+    vm.execute_next().unwrap();
+    assert_eq!(vm.execute_next(), Err(Error::StackOverflow(VM::VECTOR_TABLE - 1)));
+}
+
+case!(
+    mul, [
+        // Byte results take the whole word register
+        Instruction::movi2r(Immediate::byte(0xF0), Register::rb0()),
+        Instruction::muli2r(MulOp::Mul, Immediate::byte(0x10), Register::rb0()),
+        // Word results span the register pair
+        Instruction::movi2r(Immediate::word(0x1234), Register::r2()),
+        Instruction::muli2r(MulOp::Mul, Immediate::word(0x0100), Register::r2()),
+        // -2 * 3
+        Instruction::movi2r(Immediate::byte(0xFE), Register::rb4()),
+        Instruction::movi2r(Immediate::byte(0x03), Register::rb5()),
+        Instruction::mulr2r(MulOp::Imul, Register::rb5(), Register::rb4()),
+    ],
+    7,
+    [0x0F00, 0, 0x3400, 0x0012, 0xFFFA, 0x0003, 0, 0, 0, 0, 0, 0x8000, 0x8000, 0, 0x1D, 0]
+);
+
+case!(
+    mul_flags, [
+        Instruction::movi2r(Immediate::word(0x00FF), Register::r0()),
+        Instruction::muli2r(MulOp::Mul, Immediate::word(0x0002), Register::r0()),
+        Instruction::muli2r(MulOp::Mul, Immediate::word(0x0100), Register::r0()),
     ],
     3,
-    [0xF337, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0x0E, 0],
-    [(0xF337, 0x39), (0xF338, 0xF3), (0xF339, 0x39), (0xF33A, 0)]
+    [0xFE00, 0x0001, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0x8000, 0x8000, (Flags::CARRY | Flags::OVERFLOW).bits(), 0x0E, 0]
 );
+
+case!(
+    div, [
+        // Quotient in the low byte and remainder in the high one
+        Instruction::movi2r(Immediate::word(0x0107), Register::r0()),
+        Instruction::muli2r(MulOp::Div, Immediate::byte(0x10), Register::rb0()),
+        // 0x0001_0005 / 0x10
+        Instruction::movi2r(Immediate::word(0x0005), Register::r2()),
+        Instruction::movi2r(Immediate::word(0x0001), Register::r3()),
+        Instruction::muli2r(MulOp::Div, Immediate::word(0x0010), Register::r2()),
+        // -7 / 2
+        Instruction::movi2r(Immediate::word(0xFFF9), Register::r4()),
+        Instruction::movi2r(Immediate::byte(0x02), Register::rb5()),
+        Instruction::mulr2r(MulOp::Idiv, Register::rb5(), Register::rb4()),
+    ],
+    8,
+    [0x0710, 0, 0x1000, 0x0005, 0xFFFD, 0x0002, 0, 0, 0, 0, 0, 0x8000, 0x8000, 0, 0x21, 0]
+);
+
+#[test]
+fn div_fault() {
+    let code = [
+        Instruction::movi2r(Immediate::word(0x1000), Register::r0()),
+        Instruction::muli2r(MulOp::Div, Immediate::byte(0x00), Register::rb0()),
+        Instruction::muli2r(MulOp::Div, Immediate::byte(0x02), Register::rb0()),
+        Instruction::muli2r(MulOp::Idiv, Immediate::byte(0x40), Register::rb0()),
+    ];
+    let rom = code.into_iter().flat_map(|instr| instr.unwrap().compile()).collect();
+    let mut vm = VM::new(rom, 0x8000).unwrap();
+    vm.boot(0x8000);
+
+    // Faults stop the CPU, pick up after each one to check the next
+    assert_eq!(vm.execute_next(), Ok(()));
+    assert_eq!(vm.execute_next(), Err(Error::DivisionByZero));
+    vm.state = RunState::Running;
+    assert_eq!(vm.execute_next(), Err(Error::DivisionOverflow(Width::Byte)));
+    vm.state = RunState::Running;
+    assert_eq!(vm.execute_next(), Ok(()));
+    assert_eq!(vm.get_reg(&Register::r0()).get_word(0), 0x0040);
+}
+
+case!(
+    int_iret, [
+        Instruction::movi2ip(Immediate::word(0x000E), Immediate::word(VM::VECTOR_TABLE + 2 * 0x21)),
+        Instruction::int(Immediate::byte(0x21)),
+        Instruction::movi2r(Immediate::word(0x600D), Register::r1()),
+        // Handler
+        Instruction::movi2r(Immediate::word(0xF337), Register::r0()),
+        Instruction::iret(),
+    ],
+    5,
+    [0xF337, 0x600D, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0x8000, 0x8000, 0, 0x0E, 0],
+    [(0x8000, 0x00), (0x8001, 0x00), (0x8002, 0x0A), (0x8003, 0x00)]
+);
+
+#[test]
+fn irq() {
+    let code = [
+        Instruction::movi2ip(Immediate::word(0x000C), Immediate::word(VM::VECTOR_TABLE + 2 * (VM::IRQ_VECTORS + 3) as u16)),
+        Instruction::nop(),
+        Instruction::sti(),
+        Instruction::nop(),
+        // Handler
+        Instruction::unaryr(UnaryOp::Inc, Register::r0()),
+        Instruction::iret(),
+    ];
+    let rom = code.into_iter().flat_map(|instr| instr.unwrap().compile()).collect();
+    let mut vm = VM::new(rom, 0x8000).unwrap();
+    vm.boot(0x8000);
+
+    vm.execute_next().unwrap();
+    vm.raise_irq(3);
+
+    // Masked until sti
+    vm.execute_next().unwrap();
+    vm.execute_next().unwrap();
+    assert_eq!(vm.get_reg(&Register::rip()).get_word(0), 0x000A);
+
+    // Serviced before the next instruction, which is the first one of the handler
+    vm.execute_next().unwrap();
+    assert_eq!(vm.get_reg(&Register::r0()).get_word(0), 1);
+    assert_eq!(vm.get_reg(&Register::rip()).get_word(0), 0x000F);
+    assert!(!vm.flags().interrupt_enable());
+    assert_eq!(vm.get_reg(&Register::rsh()).get_word(0), 0x8004);
+
+    // Back where it was interrupted, with interrupts enabled again
+    vm.execute_next().unwrap();
+    assert_eq!(vm.get_reg(&Register::rip()).get_word(0), 0x000A);
+    assert!(vm.flags().interrupt_enable());
+    assert_eq!(vm.get_reg(&Register::rsh()).get_word(0), 0x8000);
+
+    vm.execute_next().unwrap();
+    assert_eq!(vm.get_reg(&Register::rip()).get_word(0), 0x000C);
+    assert_eq!(vm.get_reg(&Register::r0()).get_word(0), 1);
+}
+
+#[test]
+fn hlt() {
+    let code = [
+        Instruction::unaryr(UnaryOp::Inc, Register::r0()),
+        Instruction::hlt(),
+        Instruction::unaryr(UnaryOp::Inc, Register::r0()),
+    ];
+    let rom = code.into_iter().flat_map(|instr| instr.unwrap().compile()).collect();
+    let mut vm = VM::new(rom, 0x8000).unwrap();
+    vm.boot(0x8000);
+
+    assert_eq!(vm.run(None), StopReason::Halted);
+    assert_eq!(vm.state, RunState::Halted);
+    assert_eq!(vm.get_reg(&Register::rip()).get_word(0), 0x0005);
+
+    // Stays halted
+    vm.execute_next().unwrap();
+    assert_eq!(vm.get_reg(&Register::r0()).get_word(0), 1);
+    assert_eq!(vm.run(Some(10)), StopReason::Halted);
+}
+
+#[test]
+fn wfi() {
+    let code = [
+        Instruction::wfi(),
+        Instruction::unaryr(UnaryOp::Inc, Register::r0()),
+    ];
+    let rom = code.into_iter().flat_map(|instr| instr.unwrap().compile()).collect();
+    let mut vm = VM::new(rom, 0x8000).unwrap();
+    vm.boot(0x8000);
+
+    assert_eq!(vm.run(None), StopReason::Waiting);
+    vm.execute_next().unwrap();
+    assert_eq!(vm.get_reg(&Register::r0()).get_word(0), 0);
+
+    // A masked IRQ still wakes the CPU up, without being serviced
+    vm.raise_irq(0);
+    vm.execute_next().unwrap();
+    assert_eq!(vm.state, RunState::Running);
+    assert_eq!(vm.get_reg(&Register::r0()).get_word(0), 1);
+}
+
+#[test]
+fn run() {
+    let code = [
+        Instruction::unaryr(UnaryOp::Inc, Register::r0()),
+        Instruction::movi2r(Immediate::word(0x0007), Register::r5()),
+        Instruction::jmpr(Condition::Always, Register::r5()),
+    ];
+    let rom = code.into_iter().flat_map(|instr| instr.unwrap().compile()).collect();
+    let mut vm = VM::new(rom, 0x8000).unwrap();
+    vm.boot(0x8000);
+
+    assert_eq!(vm.run(Some(2)), StopReason::Limit);
+    assert_eq!(vm.run(None), StopReason::SelfLoop(0x0007));
+    assert_eq!(vm.get_reg(&Register::r0()).get_word(0), 1);
+
+    // Faults stop execution
+    let rom = Instruction::popr(Register::r0()).unwrap().compile();
+    let mut vm = VM::new(rom, 0x8000).unwrap();
+    vm.boot(0x8000);
+    assert_eq!(vm.run(None), StopReason::Fault(Error::StackUnderflow(0x8000)));
+    assert_eq!(vm.state, RunState::Faulted(Error::StackUnderflow(0x8000)));
+
+    // Until booted again
+    let rip = vm.get_reg(&Register::rip()).get_word(0);
+    assert_eq!(vm.execute_next(), Err(Error::StackUnderflow(0x8000)));
+    assert_eq!(vm.get_reg(&Register::rip()).get_word(0), rip);
+    assert_eq!(vm.state, RunState::Faulted(Error::StackUnderflow(0x8000)));
+    assert_eq!(vm.run(None), StopReason::Fault(Error::StackUnderflow(0x8000)));
+}
+
+case!(
+    movx, [
+        Instruction::movi2r(Immediate::word(0x8000), Register::r0()),
+        Instruction::movi2rp(Immediate::byte(0xF3), Register::r0()),
+        Instruction::movi2r(Immediate::word(0x1234), Register::r1()),
+        Instruction::movi2r(Immediate::word(0x1234), Register::r2()),
+        Instruction::movxrp2r(Extension::Zero, Register::r0(), Register::r1()),
+        Instruction::movxip2r(Extension::Sign, Immediate::word(0x8000), Register::r2()),
+        Instruction::movi2r(Immediate::byte(0x7F), Register::rb3()),
+        Instruction::movxr2r(Extension::Sign, Register::rb3(), Register::r4()),
+        Instruction::movxr2r(Extension::Sign, Register::rb2(), Register::r5()),
+    ],
+    9,
+    [0x8000, 0x00F3, 0xFFF3, 0x007F, 0x007F, 0xFFF3, 0, 0, 0, 0, 0, 0x8000, 0x8000, 0, 0x22, 0]
+);
+
+fn block_vm(code : &[Instruction]) -> VM {
+    let rom = code.iter().flat_map(|instr| instr.compile()).collect();
+    let mut vm = VM::new(rom, 0x8000).unwrap();
+    vm.boot(0x8000);
+    vm
+}
+
+#[test]
+fn block_copy_fill() {
+    let copy = Instruction::block(BlockOp::Copy, Width::Byte, Register::r0(), Register::r1(), Register::r2()).unwrap();
+    let fill = Instruction::block(BlockOp::Fill, Width::Word, Register::r3(), Register::r1(), Register::r2()).unwrap();
+    let mut vm = block_vm(&[copy.clone(), fill]);
+
+    // Copies its own encoding from the ROM to the RAM
+    vm.set_reg_value(&Register::r0(), 0x0000);
+    vm.set_reg_value(&Register::r1(), 0x8000);
+    vm.set_reg_value(&Register::r2(), 4);
+    vm.execute_next().unwrap();
+    assert_eq!((0..4).map(|offset| vm.get_mem_byte(0x8000 + offset)).collect::<Vec<_>>(), copy.compile());
+    assert_eq!(vm.regs()[0..3], [0x0004, 0x8004, 0x0000]);
+
+    // Fills across the display, IO and RAM windows
+    vm.set_reg_value(&Register::r1(), VM::IO_START - 2);
+    vm.set_reg_value(&Register::r2(), 0x0402);
+    vm.set_reg_value(&Register::r3(), 0x600D);
+    vm.execute_next().unwrap();
+    assert_eq!(vm.get_mem(VM::IO_START - 2, Width::Word), Immediate::word(0x600D));
+    assert_eq!(vm.get_mem(VM::IO_START, Width::Word), Immediate::word(0x600D));
+    assert_eq!(vm.get_mem(VM::RAM_START, Width::Word), Immediate::word(0x600D));
+    // Untouched rest of the copy
+    assert_eq!(vm.get_mem(VM::RAM_START + 0x0002, Width::Word), Immediate::word(0x0210));
+    assert_eq!(vm.regs()[1..3], [0x8002, 0x0000]);
+}
+
+#[test]
+fn block_compare_scan() {
+    let compare = Instruction::block(BlockOp::Compare, Width::Byte, Register::r0(), Register::r1(), Register::r2()).unwrap();
+    let scan = Instruction::block(BlockOp::Scan, Width::Byte, Register::rb3(), Register::r1(), Register::r2()).unwrap();
+    let mut vm = block_vm(&[compare.clone(), compare, scan.clone(), scan]);
+    for (offset, value) in b"SmplCore".iter().enumerate() {
+        vm.set_mem_byte(0x8000 + offset as u16, *value);
+    }
+    for (offset, value) in b"SmplCode".iter().enumerate() {
+        vm.set_mem_byte(0x8100 + offset as u16, *value);
+    }
+
+    // Stops right after the first difference, 'd' < 'r'
+    vm.set_reg_value(&Register::r0(), 0x8000);
+    vm.set_reg_value(&Register::r1(), 0x8100);
+    vm.set_reg_value(&Register::r2(), 8);
+    vm.execute_next().unwrap();
+    assert_eq!(vm.regs()[0..3], [0x8007, 0x8107, 0x0001]);
+    assert!(Condition::Carry.holds(vm.flags()));
+
+    // Equal blocks
+    vm.set_reg_value(&Register::r0(), 0x8000);
+    vm.set_reg_value(&Register::r1(), 0x8100);
+    vm.set_reg_value(&Register::r2(), 5);
+    vm.execute_next().unwrap();
+    assert_eq!(vm.regs()[0..3], [0x8005, 0x8105, 0x0000]);
+    assert!(vm.flags().zero());
+
+    // Finds the 'C'
+    vm.set_reg_value(&Register::r1(), 0x8000);
+    vm.set_reg_value(&Register::r2(), 8);
+    vm.set_reg_value(&Register::r3(), b'C' as u16);
+    vm.execute_next().unwrap();
+    assert_eq!(vm.regs()[1..3], [0x8005, 0x0003]);
+    assert!(vm.flags().zero());
+
+    // Doesn't find it
+    vm.set_reg_value(&Register::r3(), b'x' as u16);
+    vm.execute_next().unwrap();
+    assert_eq!(vm.regs()[1..3], [0x8008, 0x0000]);
+    assert!(!vm.flags().zero());
+}
+
+#[test]
+fn block_shared_count() {
+    // `movsb r1, r2, r2` would step the count along with the destination, so it is rejected instead of looping forever
+    let mut vm = VM::new(vec![0x80, 0x00, 0x21, 0x02], 0x8000).unwrap();
+    vm.boot(0x8000);
+    vm.set_reg_value(&Register::r2(), 4);
+    assert!(matches!(vm.run(None), StopReason::Fault(Error::InvalidOperands(_))));
+    assert_eq!(vm.regs()[2], 4);
+    assert_eq!(vm.get_reg(&Register::rip()).get_word(0), 0);
+}
+
+case!(
+    xchg, [
+        Instruction::movi2r(Immediate::word(0x600D), Register::r0()),
+        Instruction::movi2r(Immediate::word(0xF337), Register::r1()),
+        Instruction::xchgr2r(Register::r0(), Register::r1()),
+        Instruction::movi2r(Immediate::word(0x8000), Register::r2()),
+        Instruction::movi2rp(Immediate::word(0x1234), Register::r2()),
+        Instruction::xchgr2rp(Register::r0(), Register::r2()),
+        Instruction::xchgr2ip(Register::rb1(), Immediate::word(0x8001)),
+    ],
+    7,
+    [0x1234, 0x60F3, 0x8000, 0, 0, 0, 0, 0, 0, 0, 0, 0x8000, 0x8000, 0, 0x18, 0],
+    [(0x8000, 0x37), (0x8001, 0x0D)]
+);
+
+case!(
+    cmpxchg, [
+        // Equal, stores the source
+        Instruction::movi2r(Immediate::word(0x8000), Register::r2()),
+        Instruction::movi2r(Immediate::word(0x600D), Register::r1()),
+        Instruction::cmpxchgr2rp(Register::r1(), Register::r2()),
+        // Different, loads the current value
+        Instruction::movi2r(Immediate::word(0xF337), Register::r3()),
+        Instruction::cmpxchgr2r(Register::r3(), Register::r1()),
+    ],
+    5,
+    [0x600D, 0x600D, 0x8000, 0xF337, 0, 0, 0, 0, 0, 0, 0, 0x8000, 0x8000, (Flags::CARRY | Flags::SIGN).bits(), 0x10, 0],
+    [(0x8000, 0x0D), (0x8001, 0x60)]
+);
+
+// IRQs are serviced between instructions, so handlers only see completed exchanges
+#[test]
+fn xchg_irq() {
+    let code = [
+        Instruction::movi2ip(Immediate::word(0x0010), Immediate::word(VM::VECTOR_TABLE + 2 * VM::IRQ_VECTORS as u16)),
+        Instruction::sti(),
+        Instruction::movi2r(Immediate::word(0x600D), Register::r0()),
+        Instruction::xchgr2ip(Register::r0(), Immediate::word(0x8100)),
+        // Handler
+        Instruction::movip2r(Immediate::word(0x8100), Register::r1()),
+        Instruction::iret(),
+    ];
+    let rom = code.into_iter().flat_map(|instr| instr.unwrap().compile()).collect();
+    let mut vm = VM::new(rom, 0x8000).unwrap();
+    vm.boot(0x8000);
+
+    for _ in 0..4 {
+        vm.execute_next().unwrap();
+    }
+    vm.raise_irq(0);
+    vm.execute_next().unwrap();
+    assert_eq!(vm.get_reg(&Register::r0()).get_word(0), 0x0000);
+    assert_eq!(vm.get_reg(&Register::r1()).get_word(0), 0x600D);
+}
+
+case!(
+    indexed, [
+        Instruction::movi2r(Immediate::word(0x8000), Register::r0()),
+        Instruction::movi2r(Immediate::word(3), Register::r1()),
+        Instruction::movi2m(Immediate::word(0x600D), Address::new(Register::r0(), Some((Register::r1(), 2)), 4)),
+        Instruction::movm2r(Address::new(Register::r0(), None, 0x000A), Register::r2()),
+        Instruction::alui2m(AluOp::Add, Immediate::byte(1), Address::new(Register::r0(), Some((Register::r1(), 1)), 7)),
+        // Displacements wrap around, so they can go backwards
+        Instruction::movr2m(Register::rb2(), Address::new(Register::r1(), Some((Register::r0(), 1)), 0xFFFF)),
+    ],
+    6,
+    [0x8000, 3, 0x600D, 0, 0, 0, 0, 0, 0, 0, 0, 0x8000, 0x8000, 0, 0x25, 0],
+    [(0x8002, 0x0D), (0x800A, 0x0E), (0x800B, 0x60)]
+);
+
+#[test]
+fn position_independent() {
+    let code = [
+        Instruction::callrel(Immediate::word(0x0002)),
+        Instruction::hlt(),
+        Instruction::movrel2r(Immediate::word(0x0008), Register::r0()),
+        Instruction::movm2r(Address::rip_relative(0x0002), Register::r1()),
+        Instruction::ret(),
+    ];
+    let mut routine : Vec<u8> = code.into_iter().flat_map(|instr| instr.unwrap().compile()).collect();
+    routine.extend([0x0D, 0x60]);
+
+    // Runs the same bytes from the RAM, wherever they are copied
+    for start in [VM::RAM_START, 0x8100] {
+        let mut vm = VM::new(vec![], 0x8000).unwrap();
+        vm.boot(0xC000);
+        for (offset, byte) in routine.iter().enumerate() {
+            vm.set_mem_byte(start + offset as u16, *byte);
+        }
+        vm.set_reg_value(&Register::rip(), start);
+
+        assert_eq!(vm.run(None), StopReason::Halted);
+        assert_eq!(vm.get_reg(&Register::r0()).get_word(0), start + 0x12);
+        assert_eq!(vm.get_reg(&Register::r1()).get_word(0), 0x600D);
+        assert_eq!(vm.get_reg(&Register::rip()).get_word(0), start + 0x06);
+    }
+}
+
+/// Remembers what was written to it and reads back its complement
+struct Latch(std::rc::Rc<std::cell::Cell<u16>>);
+
+impl Port for Latch {
+    fn read(&mut self, _width : Width) -> u16 {
+        !self.0.get()
+    }
+
+    fn write(&mut self, _width : Width, value : u16) {
+        self.0.set(value)
+    }
+}
+
+#[test]
+fn port() {
+    let code = [
+        Instruction::movi2r(Immediate::word(0x600D), Register::r0()),
+        Instruction::outi(Register::r0(), Immediate::byte(0x10)),
+        Instruction::movi2r(Immediate::byte(0x10), Register::rb1()),
+        Instruction::inr(Register::rb1(), Register::rb2()),
+        Instruction::ini(Immediate::byte(0x10), Register::r3()),
+        Instruction::ini(Immediate::byte(0x11), Register::r4()),
+    ];
+    let rom = code.into_iter().flat_map(|instr| instr.unwrap().compile()).collect();
+    let mut vm = VM::new(rom, 0x8000).unwrap();
+    vm.boot(0x8000);
+
+    let latch = std::rc::Rc::new(std::cell::Cell::new(0));
+    vm.register_port(0x10, Latch(latch.clone()));
+    for _ in 0..5 {
+        vm.execute_next().unwrap();
+    }
+    assert_eq!(latch.get(), 0x600D);
+    assert_eq!(vm.get_reg(&Register::rb2()).get_byte(0), 0xF2);
+    assert_eq!(vm.get_reg(&Register::r3()).get_word(0), 0x9FF2);
+
+    // Nothing is at the other ports
+    assert_eq!(vm.execute_next(), Err(Error::NoSuchPort(0x11)));
+}
@@ -12,7 +12,7 @@ impl RegisterValue {
 
     pub fn set_word(&mut self, idx : u8, value : u16) {
         self.0 &= !(0xFFFF << (idx * 16)); // Set this word to 0
-        self.0 |= (value as u16) << (idx * 16); // Set the value
+        self.0 |= value << (idx * 16); // Set the value
     }
 
     pub fn get_byte(&self, idx : u8) -> u8 {
@@ -20,7 +20,7 @@ impl RegisterValue {
     }
 
     pub fn get_word(&self, idx : u8) -> u16 {
-        (self.0 >> (idx * 16)) as u16
+        self.0 >> (idx * 16)
     }
 }
 
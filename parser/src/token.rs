@@ -39,7 +39,7 @@ impl GroupDelim {
 pub enum Token {
     Group(GroupDelim, Vec<Token>),
     Ident(String),
-    Number(u16),
+    Number(i64),
     Punct(char),
     Comment(String),
 }
@@ -66,41 +66,42 @@ fn match_identifier(scanner : &mut Scanner<char>) -> Option<Token> {
         ).map(Token::Ident)
 }
 
-fn match_number(scanner : &mut Scanner<char>) -> Option<Token> {
+fn match_number(scanner : &mut Scanner<char>) -> Result<Option<Token>> {
     if scanner.test(|c| c.is_ascii_digit() || *c == '-') { // TODO: is_numeric?
         scanner.scan(|chars| match chars {
-            ['-'] => ScannerAction::Request(Token::Punct('-')),
-            ['-', digits @ ..] if digits.iter().all(|c| c.is_ascii_digit())
-                => ScannerAction::Request(Token::Number(
-                    -digits.iter().collect::<String>().parse::<i16>().unwrap() as u16
-                    // TODO: Don't lose sign
-                )),
-            ['0'] => ScannerAction::Request(Token::Number(0)),
-
-            ['0', 'x'] => ScannerAction::Require,
-            ['0', 'x', digits @ ..] if digits.iter().all(|c| c.is_ascii_hexdigit())
-                => ScannerAction::Request(Token::Number(
-                    u16::from_str_radix(&digits.iter().collect::<String>(), 16).unwrap()
-                )),
-
-            ['0', 'o'] => ScannerAction::Require,
-            ['0', 'o', digits @ ..] if digits.iter().all(|c| c.is_digit(8))
-                => ScannerAction::Request(Token::Number(
-                    u16::from_str_radix(&digits.iter().collect::<String>(), 8).unwrap()
-                )),
-
-            ['0', 'b'] => ScannerAction::Require,
-            ['0', 'b', digits @ ..] if digits.iter().all(|c| c.is_digit(2))
-                => ScannerAction::Request(Token::Number(
-                    u16::from_str_radix(&digits.iter().collect::<String>(), 2).unwrap()
-                )),
-
-            _ if chars.iter().all(|c| c.is_ascii_digit())
-                => ScannerAction::Request(Token::Number(chars.iter().collect::<String>().parse().unwrap())),
-
-            _ => ScannerAction::None,
+            ['-'] => ScannerAction::Request(Ok(Token::Punct('-'))),
+            ['-', unsigned @ ..] => match_unsigned(unsigned, -1),
+            _ => match_unsigned(chars, 1),
         }).unwrap() // TODO: Handle
-    } else { None }
+        .transpose()
+    } else { Ok(None) }
+}
+
+/// The digits of a number after its sign, which multiplies it.
+/// Numbers that don't even fit an i64 are out of bounds for any width, they report the literal as written
+fn match_unsigned(chars : &[char], sign : i64) -> ScannerAction<Result<Token>> {
+    let number = |digits : &[char], radix| ScannerAction::Request(
+        u64::from_str_radix(&digits.iter().collect::<String>(), radix).ok()
+            .and_then(|magnitude| i64::try_from(sign as i128 * magnitude as i128).ok())
+            .map(Token::Number)
+            .ok_or_else(|| Error::LiteralOOB(format!("{}{}", if sign < 0 { "-" } else { "" }, chars.iter().collect::<String>())))
+    );
+    match chars {
+        ['0'] => ScannerAction::Request(Ok(Token::Number(0))),
+
+        ['0', 'x'] => ScannerAction::Require,
+        ['0', 'x', digits @ ..] if digits.iter().all(|c| c.is_ascii_hexdigit()) => number(digits, 16),
+
+        ['0', 'o'] => ScannerAction::Require,
+        ['0', 'o', digits @ ..] if digits.iter().all(|c| c.is_digit(8)) => number(digits, 8),
+
+        ['0', 'b'] => ScannerAction::Require,
+        ['0', 'b', digits @ ..] if digits.iter().all(|c| c.is_digit(2)) => number(digits, 2),
+
+        [_, ..] if chars.iter().all(|c| c.is_ascii_digit()) => number(chars, 10),
+
+        _ => ScannerAction::None,
+    }
 }
 
 fn match_punct(scanner : &mut Scanner<char>) -> Option<Token> {
@@ -108,12 +109,12 @@ fn match_punct(scanner : &mut Scanner<char>) -> Option<Token> {
         .map(Token::Punct)
 }
 
-fn match_group(scanner : &mut Scanner<char>) -> Option<Token> {
-    let delim = scanner.transform(GroupDelim::from_open)?;
+fn match_group(scanner : &mut Scanner<char>) -> Result<Option<Token>> {
+    let Some(delim) = scanner.transform(GroupDelim::from_open) else { return Ok(None) };
 
     let mut inside = Vec::new();
     loop {
-        let Some(t) = get_token(scanner) else { panic!("Unclosed group.") }; //TODO: Handle
+        let Some(t) = get_token(scanner)? else { panic!("Unclosed group.") }; //TODO: Handle
         
         match t {
             Token::Punct(c)
@@ -122,7 +123,7 @@ fn match_group(scanner : &mut Scanner<char>) -> Option<Token> {
         }
     }
 
-    Some(Token::Group(delim, inside))
+    Ok(Some(Token::Group(delim, inside)))
 }
 
 fn match_comment(scanner : &mut Scanner<char>) -> Option<Token> {
@@ -143,29 +144,30 @@ fn match_comment(scanner : &mut Scanner<char>) -> Option<Token> {
     }).unwrap()
 }
 
-fn get_token(scanner : &mut Scanner<char>) -> Option<Token> {
+fn get_token(scanner : &mut Scanner<char>) -> Result<Option<Token>> {
     skip_whitespace(scanner);
 
-    let res = match_identifier(scanner)
-    .or_else(|| match_number(scanner))
-    .or_else(|| match_group(scanner))
-    .or_else(|| match_comment(scanner))
-    .or_else(|| match_punct(scanner));
+    let res = match_identifier(scanner).map(Ok)
+    .or_else(|| match_number(scanner).transpose())
+    .or_else(|| match_group(scanner).transpose())
+    .or_else(|| match_comment(scanner).map(Ok))
+    .or_else(|| match_punct(scanner).map(Ok))
+    .transpose()?;
 
     if let Some(Token::Comment(_)) = res {
         get_token(scanner)
     } else {
-        res
+        Ok(res)
     }
 }
 
-pub fn tokenize(code : &str) -> Vec<Token> {
+pub fn tokenize(code : &str) -> Result<Vec<Token>> {
     let mut scanner = Scanner::new(code.chars().collect());
     let mut toks = Vec::new();
-    while let Some(t) = get_token(&mut scanner) {
+    while let Some(t) = get_token(&mut scanner)? {
         toks.push(t)
     }
-    toks
+    Ok(toks)
 }
 
 #[cfg(test)]
@@ -175,7 +177,7 @@ mod tests {
     #[test]
     fn group() {
         let code = "(a) (a b) ((a)) [] {}";
-        let toks = tokenize(code);
+        let toks = tokenize(code).unwrap();
         assert_eq!(toks, vec![
             Token::Group(GroupDelim::Paren, vec![Token::Ident("a".to_string())]),
             Token::Group(GroupDelim::Paren, vec![Token::Ident("a".to_string()), Token::Ident("b".to_string())]),
@@ -190,33 +192,40 @@ mod tests {
     #[test]
     fn ident() {
         let code = " a ab  ab1   a1b   ab_    a_b     _ab ";
-        let toks = tokenize(code);
+        let toks = tokenize(code).unwrap();
         let res : Vec<_> = code.split_whitespace().map(|s| Token::Ident(s.to_string())).collect();
         assert_eq!(toks, res);
     }
 
     #[test]
     fn number() {
-        let code = "0 0x0 0o0 0b0 62263 -3273 0xF337 0o171467 0b1111001100110111";
-        let toks = tokenize(code);
+        let code = "0 0x0 0o0 0b0 62263 -3273 0xF337 0o171467 0b1111001100110111 -0x80 70000";
+        let toks = tokenize(code).unwrap();
         assert_eq!(toks, vec![
             Token::Number(0),
             Token::Number(0x0),
             Token::Number(0o0),
             Token::Number(0b0),
             Token::Number(62263),
-            Token::Number(-3273i16 as u16),
+            Token::Number(-3273),
             Token::Number(0xF337),
             Token::Number(0o171467),
             Token::Number(0b1111001100110111),
+            Token::Number(-0x80),
+            Token::Number(70000),
         ]);
+
+        assert_eq!(tokenize("-0x8000000000000000 0x7FFFFFFFFFFFFFFF"), Ok(vec![Token::Number(i64::MIN), Token::Number(i64::MAX)]));
+        assert_eq!(tokenize("0x8000000000000000"), Err(Error::LiteralOOB("0x8000000000000000".to_string())));
+        assert_eq!(tokenize("99999999999999999999"), Err(Error::LiteralOOB("99999999999999999999".to_string())));
+        assert_eq!(tokenize("mov -99999999999999999999, r0"), Err(Error::LiteralOOB("-99999999999999999999".to_string())));
     }
 
     /* TODO: Don't skip
     #[test]
     fn comment() {
         let code = "// 0 1 asd\n/* 0 1 *\n * / asd */";
-        let toks = tokenize(code);
+        let toks = tokenize(code).unwrap();
         assert_eq!(toks, vec![
             Token::Comment(" 0 1 asd".to_string()),
             Token::Comment(" 0 1 *\n * / asd ".to_string()),
@@ -227,7 +236,7 @@ mod tests {
     #[test]
     fn punct() {
         let code = "+ - * / % , ' \\";
-        let toks = tokenize(code);
+        let toks = tokenize(code).unwrap();
         assert_eq!(toks, vec![
             Token::Punct('+'),
             Token::Punct('-'),
@@ -4,15 +4,30 @@ use std::collections::HashMap;
 use common::{prelude::*, ParamIdx};
 use crate::{parse, Expr};
 
+#[derive(Default, Debug, Clone, Copy, PartialEq)]
+pub struct Options {
+    /// Label references are relative to the next instruction, so the code runs wherever it is loaded
+    pub position_independent : bool,
+}
+
 #[derive(Default, Debug, Clone, PartialEq)]
 pub struct CompileContext {
+    pub options : Options,
     pub label_defs : HashMap<String, usize>,
-    pub label_refs : Vec<(String, usize, ParamIdx)>,
+    /// Label, index of the instruction, param to replace and whether it is relative
+    pub label_refs : Vec<(String, usize, ParamIdx, bool)>,
     pub instructions : Vec<Instruction>,
 }
 
-pub fn compile_to_context(code : &str) -> Result<CompileContext> {
-    let mut ctx = CompileContext::default();
+impl CompileContext {
+    /// Refers to `label` from the instruction being compiled
+    pub fn refer(&mut self, label : &str, param_idx : ParamIdx) {
+        self.label_refs.push((label.to_owned(), self.instructions.len(), param_idx, self.options.position_independent));
+    }
+}
+
+pub fn compile_to_context(code : &str, options : Options) -> Result<CompileContext> {
+    let mut ctx = CompileContext { options, ..Default::default() };
     for expr in parse(code)?.into_iter() {
         if let Expr::Label(label) = expr {
             ctx.label_defs.insert(label, ctx.instructions.len());
@@ -35,32 +50,46 @@ fn calc_label_offsets(ctx : &CompileContext) -> Vec<u16> {
 }
 
 pub fn compile_to_instructions(code : &str) -> Result<Vec<Instruction>> {
-    let mut ctx = compile_to_context(code)?;
+    compile_to_instructions_with(code, Options::default())
+}
+
+pub fn compile_to_instructions_with(code : &str, options : Options) -> Result<Vec<Instruction>> {
+    let mut ctx = compile_to_context(code, options)?;
     let offsets = calc_label_offsets(&ctx);
     
-    // Replace labels
-    for (label, instruction_idx, param_idx) in ctx.label_refs {
+    // Replace labels, relative ones count from the end of the instruction
+    for (label, instruction_idx, param_idx, relative) in ctx.label_refs {
         let Some(offset_idx) = ctx.label_defs.get(&label) else { return Err(Error::LabelNotDefined(label.to_owned())) };
-        let offset = offsets[*offset_idx];
+        let mut offset = offsets[*offset_idx];
+        if relative {
+            let next = offsets[instruction_idx] + ctx.instructions[instruction_idx].len();
+            offset = offset.wrapping_sub(next);
+        }
         ctx.instructions[instruction_idx] = ctx.instructions[instruction_idx].clone()
-                                                .replace_imm(param_idx, offset as u16)?;
+                                                .replace_imm(param_idx, offset)?;
     }
 
     Ok(ctx.instructions)
 }
 
 pub fn compile(code : &str) -> Result<Vec<u8>> {
-    compile_to_instructions(code)
-        .map(|instructions|
-            instructions.into_iter()
-                .flat_map(|instruction| instruction.compile())
-                .collect()
-        )
+    compile_with(code, Options::default())
+}
+
+pub fn compile_with(code : &str, options : Options) -> Result<Vec<u8>> {
+    let instructions = compile_to_instructions_with(code, options)?;
+    let mut bytes = vec![0; instructions.iter().map(|instruction| instruction.len() as usize).sum()];
+    let mut offset = 0;
+    for instruction in instructions {
+        offset += instruction.encode(&mut bytes[offset..])?;
+    }
+    Ok(bytes)
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
+    use parser::{Token, GroupDelim};
 
     #[test]
     fn nop() {
@@ -84,9 +113,10 @@ mod test {
             ("mov [0x600D], rb0", Ok(vec![Instruction::movip2r(Immediate::word(0x600D), Register::rb0()).unwrap()])),
             ("mov [0x600D], r0", Ok(vec![Instruction::movip2r(Immediate::word(0x600D), Register::r0()).unwrap()])),
             ("nop\nlabel: mov [label], r0", Ok(vec![Instruction::nop().unwrap(), Instruction::movip2r(Immediate::word(0x0002), Register::r0()).unwrap()])),
-            ("mov [0x600D], [r0]", Ok(vec![Instruction::movip2rp(Immediate::word(0x600D), Register::r0()).unwrap()])),
-            ("mov [0x600D], [0xF337]", Ok(vec![Instruction::movip2ip(Immediate::word(0x600D), Immediate::word(0xF337)).unwrap()])),
-            ("nop\nlabel: mov [0x600D], [label]", Ok(vec![Instruction::nop().unwrap(), Instruction::movip2ip(Immediate::word(0x600D), Immediate::word(0x0002)).unwrap()])),
+            ("mov.w [0x600D], [r0]", Ok(vec![Instruction::movip2rp(Width::Word, Immediate::word(0x600D), Register::r0()).unwrap()])),
+            ("mov.b [0x600D], [0xF337]", Ok(vec![Instruction::movip2ip(Width::Byte, Immediate::word(0x600D), Immediate::word(0xF337)).unwrap()])),
+            ("nop\nlabel: mov.w [0x600D], [label]", Ok(vec![Instruction::nop().unwrap(), Instruction::movip2ip(Width::Word, Immediate::word(0x600D), Immediate::word(0x0002)).unwrap()])),
+            ("mov [0x600D], [0xF337]", Err(Error::AmbiguousWidth("mov".to_string()))),
             
             ("mov rb0, rb1", Ok(vec![Instruction::movr2r(Register::rb0(), Register::rb1()).unwrap()])),
             ("mov r0, r1", Ok(vec![Instruction::movr2r(Register::r0(), Register::r1()).unwrap()])),
@@ -96,15 +126,332 @@ mod test {
 
             ("mov [r0], rb1", Ok(vec![Instruction::movrp2r(Register::r0(), Register::rb1()).unwrap()])),
             ("mov [r0], r1", Ok(vec![Instruction::movrp2r(Register::r0(), Register::r1()).unwrap()])),
-            ("mov [r0], [r1]", Ok(vec![Instruction::movrp2rp(Register::r0(), Register::r1()).unwrap()])),
-            ("mov [r0], [0x600D]", Ok(vec![Instruction::movrp2ip(Register::r0(), Immediate::word(0x600D)).unwrap()])),
-            ("nop\nlabel: mov [r0], [label]", Ok(vec![Instruction::nop().unwrap(), Instruction::movrp2ip(Register::r0(), Immediate::word(0x0002)).unwrap()])),
+            ("mov.b [r0], [r1]", Ok(vec![Instruction::movrp2rp(Width::Byte, Register::r0(), Register::r1()).unwrap()])),
+            ("mov.w [r0], [0x600D]", Ok(vec![Instruction::movrp2ip(Width::Word, Register::r0(), Immediate::word(0x600D)).unwrap()])),
+            ("nop\nlabel: mov.b [r0], [label]", Ok(vec![Instruction::nop().unwrap(), Instruction::movrp2ip(Width::Byte, Register::r0(), Immediate::word(0x0002)).unwrap()])),
+            ("mov [r0], [r1]", Err(Error::AmbiguousWidth("mov".to_string()))),
+
+            ("mov.w 0x60, [r0]", Ok(vec![Instruction::movi2rp(Immediate::word(0x60), Register::r0()).unwrap()])),
+            ("mov.w r0, [r1]", Ok(vec![Instruction::movr2rp(Register::r0(), Register::r1()).unwrap()])),
+            ("mov.b r0, [r1]", Err(Error::InvalidOperands(Instruction::Binary(BinaryOp::Mov, Operand::Reg(Register::r0()), Operand::RegPtr(Register::r1()))))),
+            ("mov.q [r0], [r1]", Err(Error::UnexpectedToken("mov".to_string(), format!("{:?}", Token::Ident("q".to_string()))))),
+        ];
+
+        for (code, expect) in cases.into_iter() {
+            let instructions = compile_to_instructions(code);
+            assert_eq!(instructions, expect, "\"{code}\"");
+        }
+    }
+
+    #[test]
+    fn alu() {
+        let cases = vec![
+            ("add 0x60, rb0", Ok(vec![Instruction::alui2r(AluOp::Add, Immediate::byte(0x60), Register::rb0()).unwrap()])),
+            ("sub 0x600D, [r0]", Ok(vec![Instruction::alui2rp(AluOp::Sub, Immediate::word(0x600D), Register::r0()).unwrap()])),
+            ("nop\nlabel: adc 0x600D, [label]", Ok(vec![Instruction::nop().unwrap(), Instruction::alui2ip(AluOp::Adc, Immediate::word(0x600D), Immediate::word(0x0002)).unwrap()])),
+            ("sbb [0x600D], r0", Ok(vec![Instruction::aluip2r(AluOp::Sbb, Immediate::word(0x600D), Register::r0()).unwrap()])),
+            ("add rb0, rb1", Ok(vec![Instruction::alur2r(AluOp::Add, Register::rb0(), Register::rb1()).unwrap()])),
+            ("sub r0, r2", Ok(vec![Instruction::alur2r(AluOp::Sub, Register::r0(), Register::r2()).unwrap()])),
+            ("nop\nlabel: add r0, [label]", Ok(vec![Instruction::nop().unwrap(), Instruction::alur2ip(AluOp::Add, Register::r0(), Immediate::word(0x0002)).unwrap()])),
+            ("sub.b [r0], [r1]", Ok(vec![Instruction::alurp2rp(AluOp::Sub, Width::Byte, Register::r0(), Register::r1()).unwrap()])),
+            ("add.w [0x600D], [r1]", Ok(vec![Instruction::aluip2rp(AluOp::Add, Width::Word, Immediate::word(0x600D), Register::r1()).unwrap()])),
+            ("sub [r0], [r1]", Err(Error::AmbiguousWidth("sub".to_string()))),
+            ("add rb0, r1", Err(Error::InvalidOperands(Instruction::Binary(BinaryOp::Alu(AluOp::Add), Operand::Reg(Register::rb0()), Operand::Reg(Register::r1()))))),
+
+            ("and 0x0F, rb0", Ok(vec![Instruction::alui2r(AluOp::And, Immediate::byte(0x0F), Register::rb0()).unwrap()])),
+            ("or r0, [0x7800]", Ok(vec![Instruction::alur2ip(AluOp::Or, Register::r0(), Immediate::word(0x7800)).unwrap()])),
+            ("xor r1, r1", Ok(vec![Instruction::alur2r(AluOp::Xor, Register::r1(), Register::r1()).unwrap()])),
+            ("shl 4, r0", Ok(vec![Instruction::alui2r(AluOp::Shl, Immediate::word(4), Register::r0()).unwrap()])),
+            ("shr rb1, rb0", Ok(vec![Instruction::alur2r(AluOp::Shr, Register::rb1(), Register::rb0()).unwrap()])),
+            ("sar 1, [r0]", Ok(vec![Instruction::alui2rp(AluOp::Sar, Immediate::byte(1), Register::r0()).unwrap()])),
+            ("rol 8, r2", Ok(vec![Instruction::alui2r(AluOp::Rol, Immediate::word(8), Register::r2()).unwrap()])),
+            ("ror [r0], r2", Ok(vec![Instruction::alurp2r(AluOp::Ror, Register::r0(), Register::r2()).unwrap()])),
+
+            ("cmp 0x600D, r0", Ok(vec![Instruction::alui2r(AluOp::Cmp, Immediate::word(0x600D), Register::r0()).unwrap()])),
+            ("test rb1, [r0]", Ok(vec![Instruction::alur2rp(AluOp::Test, Register::rb1(), Register::r0()).unwrap()])),
+        ];
+
+        for (code, expect) in cases.into_iter() {
+            let instructions = compile_to_instructions(code);
+            assert_eq!(instructions, expect, "\"{code}\"");
+        }
+    }
+
+    #[test]
+    fn unary() {
+        let cases = vec![
+            ("inc rb0", Ok(vec![Instruction::unaryr(UnaryOp::Inc, Register::rb0()).unwrap()])),
+            ("dec.b [r0]", Ok(vec![Instruction::unaryrp(UnaryOp::Dec, Width::Byte, Register::r0()).unwrap()])),
+            ("nop\nlabel: inc.w [label]", Ok(vec![Instruction::nop().unwrap(), Instruction::unaryip(UnaryOp::Inc, Width::Word, Immediate::word(0x0002)).unwrap()])),
+            ("not.w [0x7800]", Ok(vec![Instruction::unaryip(UnaryOp::Not, Width::Word, Immediate::word(0x7800)).unwrap()])),
+            ("inc.w r0", Ok(vec![Instruction::unaryr(UnaryOp::Inc, Register::r0()).unwrap()])),
+            ("not [0x7800]", Err(Error::AmbiguousWidth("not".to_string()))),
+            ("inc.b r0", Err(Error::InvalidOperands(Instruction::Unary(UnaryOp::Inc, Operand::Reg(Register::r0()))))),
+            ("jmp.w r0", Err(Error::UnexpectedToken("jmp".to_string(), format!("{:?}", Width::Word)))),
+            ("dec 0x600D", Err(Error::InvalidOperands(Instruction::Unary(UnaryOp::Dec, Operand::Imm(Immediate::word(0x600D)))))),
+        ];
+
+        for (code, expect) in cases.into_iter() {
+            let instructions = compile_to_instructions(code);
+            assert_eq!(instructions, expect, "\"{code}\"");
+        }
+    }
+
+    #[test]
+    fn jmp() {
+        let cases = vec![
+            ("jmp 0x600D", Ok(vec![Instruction::jmpi(Condition::Always, Immediate::word(0x600D)).unwrap()])),
+            ("jmp r5", Ok(vec![Instruction::jmpr(Condition::Always, Register::r5()).unwrap()])),
+            ("jnz [r5]", Ok(vec![Instruction::jmprp(Condition::NotZero, Register::r5()).unwrap()])),
+            ("jge [0x600D]", Ok(vec![Instruction::jmpip(Condition::GreaterEqual, Immediate::word(0x600D)).unwrap()])),
+            ("nop\nloop: dec r0\njz loop", Ok(vec![Instruction::nop().unwrap(), Instruction::unaryr(UnaryOp::Dec, Register::r0()).unwrap(), Instruction::jmpi(Condition::Zero, Immediate::word(0x0002)).unwrap()])),
+            ("ja end\nnop\nend: nop", Ok(vec![Instruction::jmpi(Condition::Above, Immediate::word(0x0007)).unwrap(), Instruction::nop().unwrap(), Instruction::nop().unwrap()])),
+            ("nop\ntable: jl [table]", Ok(vec![Instruction::nop().unwrap(), Instruction::jmpip(Condition::Less, Immediate::word(0x0002)).unwrap()])),
+            ("jmp rb5", Err(Error::InvalidOperands(Instruction::Jmp(Condition::Always, Operand::Reg(Register::rb5()))))),
+            ("jz nowhere", Err(Error::LabelNotDefined("nowhere".to_string()))),
+        ];
+
+        for (code, expect) in cases.into_iter() {
+            let instructions = compile_to_instructions(code);
+            assert_eq!(instructions, expect, "\"{code}\"");
+        }
+    }
+
+    #[test]
+    fn stack() {
+        let cases = vec![
+            ("push 0x60", Ok(vec![Instruction::pushi(Immediate::word(0x60)).unwrap()])),
+            ("push.b 0x60", Ok(vec![Instruction::pushi(Immediate::byte(0x60)).unwrap()])),
+            ("push rb0", Ok(vec![Instruction::pushr(Register::rb0()).unwrap()])),
+            ("push.b rb0", Err(Error::UnexpectedToken("push".to_string(), format!("{:?}", Width::Byte)))),
+            ("nop\nlabel: push label", Ok(vec![Instruction::nop().unwrap(), Instruction::pushi(Immediate::word(0x0002)).unwrap()])),
+            ("pop r1", Ok(vec![Instruction::popr(Register::r1()).unwrap()])),
+            ("pop 0x60", Err(Error::InvalidOperands(Instruction::Pop(Operand::Imm(Immediate::byte(0x60)))))),
+        ];
+
+        for (code, expect) in cases.into_iter() {
+            let instructions = compile_to_instructions(code);
+            assert_eq!(instructions, expect, "\"{code}\"");
+        }
+    }
+
+    #[test]
+    fn call() {
+        let cases = vec![
+            ("call 0x600D", Ok(vec![Instruction::calli(Immediate::word(0x600D)).unwrap()])),
+            ("call r5", Ok(vec![Instruction::callr(Register::r5()).unwrap()])),
+            ("call [r5]", Ok(vec![Instruction::callrp(Register::r5()).unwrap()])),
+            ("call [0x600D]", Ok(vec![Instruction::callip(Immediate::word(0x600D)).unwrap()])),
+            ("call function\nnop\nfunction: ret", Ok(vec![Instruction::calli(Immediate::word(0x0006)).unwrap(), Instruction::nop().unwrap(), Instruction::ret().unwrap()])),
+            ("call missing", Err(Error::LabelNotDefined("missing".to_string()))),
+        ];
+
+        for (code, expect) in cases.into_iter() {
+            let instructions = compile_to_instructions(code);
+            assert_eq!(instructions, expect, "\"{code}\"");
+        }
+    }
+
+    #[test]
+    fn interrupts() {
+        let cases = vec![
+            ("int 0x21", Ok(vec![Instruction::int(Immediate::byte(0x21)).unwrap()])),
+            ("sti\ncli\niret", Ok(vec![Instruction::sti().unwrap(), Instruction::cli().unwrap(), Instruction::iret().unwrap()])),
+            ("wfi\nhlt", Ok(vec![Instruction::wfi().unwrap(), Instruction::hlt().unwrap()])),
+            ("int 0x600D", Err(Error::NumberOOB(0x600D, Width::Byte))),
+            ("int r0", Err(Error::UnexpectedToken("int".to_string(), format!("{:?}", Token::Ident("r0".to_string()))))),
+        ];
+
+        for (code, expect) in cases.into_iter() {
+            let instructions = compile_to_instructions(code);
+            assert_eq!(instructions, expect, "\"{code}\"");
+        }
+    }
+
+    #[test]
+    fn movx() {
+        let cases = vec![
+            ("movzx rb0, r1", Ok(vec![Instruction::movxr2r(Extension::Zero, Register::rb0(), Register::r1()).unwrap()])),
+            ("movsx [0x8000], r1", Ok(vec![Instruction::movxip2r(Extension::Sign, Immediate::word(0x8000), Register::r1()).unwrap()])),
+            ("movsx [r0], r1", Ok(vec![Instruction::movxrp2r(Extension::Sign, Register::r0(), Register::r1()).unwrap()])),
+            ("data: movzx [data], r1", Ok(vec![Instruction::movxip2r(Extension::Zero, Immediate::word(0x0000), Register::r1()).unwrap()])),
+            ("movzx r0, r1", Err(Error::InvalidOperands(Instruction::Binary(BinaryOp::Movx(Extension::Zero), Operand::Reg(Register::r0()), Operand::Reg(Register::r1()))))),
+            ("movzx 0x60, r1", Err(Error::InvalidOperands(Instruction::Binary(BinaryOp::Movx(Extension::Zero), Operand::Imm(Immediate::word(0x60)), Operand::Reg(Register::r1()))))),
+        ];
+
+        for (code, expect) in cases.into_iter() {
+            let instructions = compile_to_instructions(code);
+            assert_eq!(instructions, expect, "\"{code}\"");
+        }
+    }
+
+    #[test]
+    fn block() {
+        let cases = vec![
+            ("movsb r0, r1, r2", Ok(vec![Instruction::block(BlockOp::Copy, Width::Byte, Register::r0(), Register::r1(), Register::r2()).unwrap()])),
+            ("stosb rb0, r1, r2", Ok(vec![Instruction::block(BlockOp::Fill, Width::Byte, Register::rb0(), Register::r1(), Register::r2()).unwrap()])),
+            ("cmpsw r0, r1, r2", Ok(vec![Instruction::block(BlockOp::Compare, Width::Word, Register::r0(), Register::r1(), Register::r2()).unwrap()])),
+            ("scasw r0, r1, r2", Ok(vec![Instruction::block(BlockOp::Scan, Width::Word, Register::r0(), Register::r1(), Register::r2()).unwrap()])),
+            ("stosb r0, r1, r2", Err(Error::InvalidOperands(Instruction::Block(BlockOp::Fill, Width::Byte, Register::r0(), Register::r1(), Register::r2())))),
+            ("movsw r0, [r1], r2", Err(Error::UnexpectedToken("block".to_string(), format!("{:?}", Token::Group(GroupDelim::Brack, vec![Token::Ident("r1".to_string())]))))),
+        ];
+
+        for (code, expect) in cases.into_iter() {
+            let instructions = compile_to_instructions(code);
+            assert_eq!(instructions, expect, "\"{code}\"");
+        }
+    }
+
+    #[test]
+    fn xchg() {
+        let cases = vec![
+            ("xchg r0, r1", Ok(vec![Instruction::xchgr2r(Register::r0(), Register::r1()).unwrap()])),
+            ("xchg rb0, [r1]", Ok(vec![Instruction::xchgr2rp(Register::rb0(), Register::r1()).unwrap()])),
+            ("xchg [r1], rb0", Ok(vec![Instruction::xchgr2rp(Register::rb0(), Register::r1()).unwrap()])),
+            ("xchg r0, [0x8000]", Ok(vec![Instruction::xchgr2ip(Register::r0(), Immediate::word(0x8000)).unwrap()])),
+            ("lock: xchg [lock], r0", Ok(vec![Instruction::xchgr2ip(Register::r0(), Immediate::word(0x0000)).unwrap()])),
+            ("cmpxchg r1, r2", Ok(vec![Instruction::cmpxchgr2r(Register::r1(), Register::r2()).unwrap()])),
+            ("cmpxchg r1, [r2]", Ok(vec![Instruction::cmpxchgr2rp(Register::r1(), Register::r2()).unwrap()])),
+            ("lock: cmpxchg r1, [lock]", Ok(vec![Instruction::cmpxchgr2ip(Register::r1(), Immediate::word(0x0000)).unwrap()])),
+            ("xchg.b [r0], [r1]", Err(Error::InvalidOperands(Instruction::BinaryMem(BinaryOp::Xchg, Width::Byte, Operand::RegPtr(Register::r0()), Operand::RegPtr(Register::r1()))))),
+            ("cmpxchg [r1], r2", Err(Error::InvalidOperands(Instruction::Binary(BinaryOp::Cmpxchg, Operand::RegPtr(Register::r1()), Operand::Reg(Register::r2()))))),
+        ];
+
+        for (code, expect) in cases.into_iter() {
+            let instructions = compile_to_instructions(code);
+            assert_eq!(instructions, expect, "\"{code}\"");
+        }
+    }
+
+    #[test]
+    fn mul() {
+        let cases = vec![
+            ("mul 0x60, rb0", Ok(vec![Instruction::muli2r(MulOp::Mul, Immediate::byte(0x60), Register::rb0()).unwrap()])),
+            ("imul [0x600D], r0", Ok(vec![Instruction::mulip2r(MulOp::Imul, Immediate::word(0x600D), Register::r0()).unwrap()])),
+            ("div r1, r2", Ok(vec![Instruction::mulr2r(MulOp::Div, Register::r1(), Register::r2()).unwrap()])),
+            ("idiv [r1], rb2", Ok(vec![Instruction::mulrp2r(MulOp::Idiv, Register::r1(), Register::rb2()).unwrap()])),
+            ("mul r1, r10", Err(Error::InvalidOperands(Instruction::Binary(BinaryOp::Mul(MulOp::Mul), Operand::Reg(Register::r1()), Operand::Reg(Register::r10()))))),
+            ("mul r1, [r2]", Err(Error::InvalidOperands(Instruction::Binary(BinaryOp::Mul(MulOp::Mul), Operand::Reg(Register::r1()), Operand::RegPtr(Register::r2()))))),
+        ];
+
+        for (code, expect) in cases.into_iter() {
+            let instructions = compile_to_instructions(code);
+            assert_eq!(instructions, expect, "\"{code}\"");
+        }
+    }
+
+    #[test]
+    fn indexed() {
+        let base = Register::r0();
+        let cases = vec![
+            ("mov [r0 + 4], r1", Ok(vec![Instruction::movm2r(Address::new(base, None, 4), Register::r1()).unwrap()])),
+            ("mov rb1, [r0 + r2]", Ok(vec![Instruction::movr2m(Register::rb1(), Address::new(base, Some((Register::r2(), 1)), 0)).unwrap()])),
+            ("add 0x600D, [r2*4 + r0 - 2]", Ok(vec![Instruction::alui2m(AluOp::Add, Immediate::word(0x600D), Address::new(base, Some((Register::r2(), 4)), 0xFFFE)).unwrap()])),
+            ("xchg [r0 + r1*2 + 6], r3", Ok(vec![Instruction::xchgr2m(Register::r3(), Address::new(base, Some((Register::r1(), 2)), 6)).unwrap()])),
+            ("nop\ntable: jmp [r0 + r1*2 + table]", Ok(vec![Instruction::nop().unwrap(), Instruction::jmpm(Condition::Always, Address::new(base, Some((Register::r1(), 2)), 0x0002)).unwrap()])),
+            ("mov [r0-2], r1", Ok(vec![Instruction::movm2r(Address::new(base, None, 0xFFFE), Register::r1()).unwrap()])),
+            ("mov [r0 + r1*2-0x10], r3", Ok(vec![Instruction::movm2r(Address::new(base, Some((Register::r1(), 2)), 0xFFF0), Register::r3()).unwrap()])),
+            ("mov [0x8000-2], r0", Ok(vec![Instruction::movip2r(Immediate::word(0x7FFE), Register::r0()).unwrap()])),
+            ("mov [0x8000 + 2], r0", Ok(vec![Instruction::movip2r(Immediate::word(0x8002), Register::r0()).unwrap()])),
+            ("mov [r0 + r1*3], r2", Err(Error::InvalidOperands(Instruction::Binary(BinaryOp::Mov, Operand::Indexed(Address::new(base, Some((Register::r1(), 3)), 0)), Operand::Reg(Register::r2()))))),
+            ("mov [r0 + r1 + r2], r3", Err(Error::UnexpectedToken("mov".to_string(), format!("{:?}", Token::Group(GroupDelim::Brack, vec![
+                Token::Ident("r0".to_string()), Token::Punct('+'), Token::Ident("r1".to_string()), Token::Punct('+'), Token::Ident("r2".to_string()),
+            ]))))),
+            ("mov [r0 - r1], r2", Err(Error::UnexpectedToken("mov".to_string(), format!("{:?}", Token::Ident("r1".to_string()))))),
+        ];
+
+        for (code, expect) in cases.into_iter() {
+            let instructions = compile_to_instructions(code);
+            assert_eq!(instructions, expect, "\"{code}\"");
+        }
+    }
+
+    #[test]
+    fn position_independent() {
+        let options = Options { position_independent : true };
+        let cases = vec![
+            ("loop: dec r0\njnz loop", Ok(vec![Instruction::unaryr(UnaryOp::Dec, Register::r0()).unwrap(), Instruction::jmprel(Condition::NotZero, Immediate::word(0xFFF8)).unwrap()])),
+            ("call sub\nhlt\nsub: ret", Ok(vec![Instruction::callrel(Immediate::word(0x0002)).unwrap(), Instruction::hlt().unwrap(), Instruction::ret().unwrap()])),
+            ("mov data, r0\nmov [data], r1\nmov [data + r2*2], rb3\ndata: nop", Ok(vec![
+                Instruction::movrel2r(Immediate::word(0x000C), Register::r0()).unwrap(),
+                Instruction::movm2r(Address::rip_relative(0x0006), Register::r1()).unwrap(),
+                Instruction::movm2r(Address::new(Register::rip(), Some((Register::r2(), 2)), 0x0000), Register::rb3()).unwrap(),
+                Instruction::nop().unwrap(),
+            ])),
+            ("mov 0x600D, [r0 + 2]", Ok(vec![Instruction::movi2m(Immediate::word(0x600D), Address::new(Register::r0(), None, 2)).unwrap()])),
+            ("push label\nlabel: nop", Err(Error::InvalidOperands(Instruction::Push(Operand::Rel(Immediate::word(0)))))),
+        ];
+
+        for (code, expect) in cases.into_iter() {
+            let instructions = compile_to_instructions_with(code, options);
+            assert_eq!(instructions, expect, "\"{code}\"");
+        }
+    }
+
+    #[test]
+    fn port() {
+        let cases = vec![
+            ("in 0x10, rb0", Ok(vec![Instruction::ini(Immediate::byte(0x10), Register::rb0()).unwrap()])),
+            ("in rb1, r0", Ok(vec![Instruction::inr(Register::rb1(), Register::r0()).unwrap()])),
+            ("out r2, 0xFF", Ok(vec![Instruction::outi(Register::r2(), Immediate::byte(0xFF)).unwrap()])),
+            ("out rb2, rb3", Ok(vec![Instruction::outr(Register::rb2(), Register::rb3()).unwrap()])),
+            ("in 0x100, r0", Err(Error::NumberOOB(0x100, Width::Byte))),
+            ("in r1, r0", Err(Error::InvalidOperands(Instruction::In(Operand::Reg(Register::r1()), Register::r0())))),
+            ("out [r0], 0x10", Err(Error::UnexpectedToken("out".to_string(), format!("{:?}", Token::Group(GroupDelim::Brack, vec![Token::Ident("r0".to_string())]))))),
+        ];
+
+        for (code, expect) in cases.into_iter() {
+            let instructions = compile_to_instructions(code);
+            assert_eq!(instructions, expect, "\"{code}\"");
+        }
+    }
+
+    #[test]
+    fn display() {
+        let instructions = [
+            Instruction::movi2rp(Immediate::word(0x0060), Register::r0()),
+            Instruction::movip2ip(Width::Word, Immediate::word(0x600D), Immediate::word(0xF337)),
+            Instruction::alum2r(AluOp::Sub, Address::new(Register::r0(), Some((Register::r1(), 4)), 0xFFFE), Register::r2()),
+            Instruction::unarym(UnaryOp::Inc, Width::Byte, Address::rip_relative(0x10)),
+            Instruction::jmprel(Condition::NotZero, Immediate::word(0xFFF0)),
+            Instruction::pushi(Immediate::byte(0x60)),
+            Instruction::outi(Register::rb0(), Immediate::byte(0x10)),
+            Instruction::block(BlockOp::Copy, Width::Byte, Register::r0(), Register::r1(), Register::r2()),
+        ];
+
+        for instr in instructions {
+            let instr = instr.unwrap();
+            let code = instr.to_string();
+            assert_eq!(compile_to_instructions(&code), Ok(vec![instr]), "\"{code}\"");
+        }
+    }
+
+    #[test]
+    fn signed() {
+        let cases = vec![
+            ("mov -2, rb0", Ok(vec![Instruction::movi2r(Immediate::byte(0xFE), Register::rb0()).unwrap()])),
+            ("mov -2, r0", Ok(vec![Instruction::movi2r(Immediate::word(0xFFFE), Register::r0()).unwrap()])),
+            ("mov 200, r0", Ok(vec![Instruction::movi2r(Immediate::word(0x00C8), Register::r0()).unwrap()])),
+            ("mov -0x8000, r0", Ok(vec![Instruction::movi2r(Immediate::word(0x8000), Register::r0()).unwrap()])),
+            ("push -1", Ok(vec![Instruction::pushi(Immediate::word(0xFFFF)).unwrap()])),
+            ("mov -129, rb0", Err(Error::NumberOOB(-129, Width::Byte))),
+            ("mov -40000, r0", Err(Error::NumberOOB(-40000, Width::Word))),
+            ("mov 70000, r0", Err(Error::NumberOOB(70000, Width::Word))),
+            ("int -200", Err(Error::NumberOOB(-200, Width::Byte))),
+            ("mov -0x8000000000000000, r0", Err(Error::NumberOOB(i64::MIN, Width::Word))),
+            ("mov 99999999999999999999, r0", Err(Error::LiteralOOB("99999999999999999999".to_string()))),
         ];
 
         for (code, expect) in cases.into_iter() {
             let instructions = compile_to_instructions(code);
             assert_eq!(instructions, expect, "\"{code}\"");
         }
+
+        // The sign survives printing
+        let instructions = compile_to_instructions("mov -2, r0\nmov.b -128, [r1]").unwrap();
+        let code : Vec<_> = instructions.iter().map(Instruction::to_string).collect();
+        assert_eq!(code, ["mov -0x0002, r0", "mov -0x80, [r1]"]);
+        assert_eq!(compile_to_instructions(&code.join("\n")), Ok(instructions));
     }
 
     #[test]
@@ -6,117 +6,247 @@ use parser::{Token, GroupDelim};
 #[derive(Debug, Clone, PartialEq)]
 pub enum Expr {
     Label(String),
-
-    Nop,
-    Mov(Token, Token),
+    /// The width tag, if any, and as many operands as the mnemonic takes
+    Instruction(Mnemonic, Option<Width>, Vec<Token>),
 }
 
-macro_rules! to_instructions {
-    (
-        fn $ident:ident($left:ident : $left_type:ident, $right:ident : $right_type:ident, $ctx:ident)
-            $match:ident, $first:ident
-            {$on_i:expr} {$on_ip:expr} {$on_r:expr} {$on_rp:expr}
-    ) => {
-        fn $ident($left : &$left_type, $right : &$right_type, $ctx : &mut CompileContext) -> Result<Vec<Instruction>> {
-            match $match {
-                Token::Number($match) => { $on_i },
-                Token::Ident(ident) => {
-                    if let Some($match) = Register::from(ident) {
-                        $on_r
-                    } else {
-                        $ctx.label_refs.push((ident.to_owned(), $ctx.instructions.len(), ParamIdx::$first));
-                        let $match = &0;
-                        $on_i
-                    }
+impl Expr {
+    pub fn to_instructions(&self, ctx : &mut CompileContext) -> Result<Vec<Instruction>> {
+        use common::Mnemonic::*;
+        let Expr::Instruction(mnemonic, width, params) = self else {
+            return Ok(vec![]); // TODO: Error, panic?
+        };
+
+        if let (Some(op), [src, dest]) = (mnemonic.binary_op(), params.as_slice()) {
+            return Self::binary(op, *width, src, dest, ctx, mnemonic);
+        }
+        if let (Unary(op), [dest]) = (mnemonic, params.as_slice()) {
+            return Self::unary(*op, *width, dest, ctx, mnemonic);
+        }
+        if let (Push, [src]) = (mnemonic, params.as_slice()) {
+            return Self::push(*width, src, ctx, mnemonic);
+        }
+        if let Some(width) = width {
+            return Err(Error::UnexpectedToken(mnemonic.to_string(), format!("{width:?}")));
+        }
+
+        match (mnemonic, params.as_slice()) {
+            (Nop, []) => Ok(vec![Instruction::nop()?]),
+            (Jmp(_) | Call | Pop, [operand]) => Self::single(operand, ctx, mnemonic),
+            (Ret, []) => Ok(vec![Instruction::ret()?]),
+            (Int, [vector]) => Self::int(vector),
+            (Iret, []) => Ok(vec![Instruction::iret()?]),
+            (Sti, []) => Ok(vec![Instruction::sti()?]),
+            (Cli, []) => Ok(vec![Instruction::cli()?]),
+            (Hlt, []) => Ok(vec![Instruction::hlt()?]),
+            (Wfi, []) => Ok(vec![Instruction::wfi()?]),
+            (Block(op, width), [src, dest, count]) => Self::block(src, dest, count, op, width),
+            (In, [port, dest]) => {
+                let port = Self::port(port, ParamIdx::FirstImm, ctx, mnemonic)?;
+                Ok(vec![Instruction::input(port, Self::register(dest, mnemonic)?)?])
+            },
+            (Out, [src, port]) => {
+                let port = Self::port(port, ParamIdx::SecondImm, ctx, mnemonic)?;
+                Ok(vec![Instruction::output(Self::register(src, mnemonic)?, port)?])
+            },
+            _ => Err(Error::MissingToken(mnemonic.to_string())),
+        }
+    }
+
+    fn operand(tok : &Token, param_idx : ParamIdx, ctx : &mut CompileContext, mnemonic : &Mnemonic) -> Result<Operand> {
+        match tok {
+            Token::Number(value) => Ok(Operand::Imm(Immediate::signed(Width::smallest_that_fits(*value), *value)?)),
+            Token::Ident(ident) => match Register::from(ident) {
+                Some(reg) => Ok(Operand::Reg(reg)),
+                None if ctx.options.position_independent => {
+                    ctx.refer(ident, param_idx);
+                    Ok(Operand::Rel(Immediate::word(0)))
                 },
-                Token::Group(GroupDelim::Brack, toks) if toks.len() == 1 => {
-                    match &toks[0] {
-                        Token::Number($match) => { $on_ip },
-                        Token::Ident(ident) => {
-                            if let Some($match) = Register::from(ident) {
-                                $on_rp
-                            } else {
-                                $ctx.label_refs.push((ident.to_owned(), $ctx.instructions.len(), ParamIdx::$first));
-                                let $match = &0;
-                                $on_ip
-                            }
-                        },
-                        _ => todo!("{:?}", $match),
-                    }
+                None => {
+                    ctx.refer(ident, param_idx);
+                    Ok(Operand::Imm(Immediate::byte(0)))
+                },
+            },
+            Token::Group(GroupDelim::Brack, toks) if toks.len() == 1 => match Self::operand(&toks[0], param_idx, ctx, mnemonic)? {
+                Operand::Imm(addr) => Ok(Operand::ImmPtr(Immediate::word(addr.resize(Width::Word)?.get_value()))),
+                Operand::Reg(reg) => Ok(Operand::RegPtr(reg)),
+                Operand::Rel(_) => Ok(Operand::Indexed(Address::rip_relative(0))),
+                _ => Err(Error::UnexpectedToken(mnemonic.to_string(), format!("{tok:?}"))),
+            },
+            Token::Group(GroupDelim::Brack, toks) => Self::address(toks, param_idx, ctx, mnemonic),
+            // `(rip + disp)`, how a relative operand is written without a label
+            Token::Group(GroupDelim::Paren, toks) if toks.iter().all(|tok| !matches!(tok, Token::Ident(ident) if Register::from(ident).is_none())) => {
+                match Self::address(toks, param_idx, ctx, mnemonic)? {
+                    Operand::Indexed(Address { base : Register::RIP, index : None, disp }) => Ok(Operand::Rel(disp)),
+                    _ => Err(Error::UnexpectedToken(mnemonic.to_string(), format!("{tok:?}"))),
                 }
-                _ => todo!("{:?}", $match),
+            },
+            _ => Err(Error::UnexpectedToken(mnemonic.to_string(), format!("{tok:?}"))),
+        }
+    }
+
+    /// `[base + index * scale + disp]`, the terms may come in any order and be added or subtracted.
+    /// An unscaled index follows the base, numbers are added up into the displacement,
+    /// and a label may stand for the displacement when there are no numbers.
+    /// In position independent code labels are relative to RIP, which takes the place of the base
+    fn address(group : &[Token], param_idx : ParamIdx, ctx : &mut CompileContext, mnemonic : &Mnemonic) -> Result<Operand> {
+        let unexpected = |tok : &Token| Error::UnexpectedToken(mnemonic.to_string(), format!("{tok:?}"));
+
+        let mut regs = Vec::new();
+        let mut disp : Option<u16> = None;
+        let mut label = None;
+        let mut toks = group.iter().peekable();
+        let mut negative = false;
+        loop {
+            let Some(tok) = toks.next() else { return Err(Error::MissingToken(mnemonic.to_string())) };
+            let reg = if let Token::Ident(ident) = tok { Register::from(ident) } else { None };
+            match (tok, reg) {
+                (_, Some(reg)) if !negative => {
+                    let scale = if toks.next_if_eq(&&Token::Punct('*')).is_some() {
+                        let Some(Token::Number(scale)) = toks.next() else { return Err(Error::MissingToken(mnemonic.to_string())) };
+                        Some(u8::try_from(*scale).map_err(|_| Error::NumberOOB(*scale, Width::Byte))?)
+                    } else {
+                        None
+                    };
+                    regs.push((reg, scale));
+                },
+                (Token::Number(value), _) => {
+                    let value = Immediate::signed(Width::Word, *value)?.get_value();
+                    let disp = disp.get_or_insert(0);
+                    *disp = if negative { disp.wrapping_sub(value) } else { disp.wrapping_add(value) };
+                },
+                (Token::Ident(ident), None) if !negative && label.is_none() => label = Some(ident),
+                _ => return Err(unexpected(tok)),
+            }
+
+            // The tokenizer reads the `-2` of `[r0-2]` as a negative number, adding it subtracts
+            if matches!(toks.peek(), Some(Token::Number(value)) if *value < 0) {
+                negative = false;
+                continue;
+            }
+            match toks.next() {
+                None => break,
+                Some(Token::Punct('+')) => negative = false,
+                Some(Token::Punct('-')) => negative = true,
+                Some(tok) => return Err(unexpected(tok)),
             }
         }
-    };
-
-    (
-        fn $ident:ident($left:ident : $left_type:ident, $right:ident : $right_type:ident, $ctx:ident) FIRST
-        {$on_i:expr} {$on_ip:expr} {$on_r:expr} {$on_rp:expr}
-    ) => { to_instructions!(fn $ident($left : $left_type, $right : $right_type, $ctx) $left, FirstImm
-            { $on_i } { $on_ip } { $on_r } { $on_rp }
-    );};
-
-    (
-        fn $ident:ident($left:ident : $left_type:ident, $right:ident : $right_type:ident, $ctx:ident) SECOND
-        {$on_i:expr} {$on_ip:expr} {$on_r:expr} {$on_rp:expr}
-    ) => { to_instructions!(fn $ident($left : $left_type, $right : $right_type, $ctx) $right, SecondImm
-            { $on_i } { $on_ip } { $on_r } { $on_rp }
-    );};
-}
 
-impl Expr {
-    pub fn to_instructions(&self, ctx : &mut CompileContext) -> Result<Vec<Instruction>> {
-        use Expr::*;
-        match self {
-            Label(_) => Ok(vec![]), // TODO: Error, panic?
-            Nop => Ok(vec![Instruction::nop()?]),
-            Mov(src, dest) => Self::mov(src, dest, ctx),
+        let disp = match (label, disp) {
+            (Some(label), None) => {
+                ctx.refer(label, param_idx);
+                0
+            },
+            (Some(label), Some(_)) => return Err(unexpected(&Token::Ident(label.to_owned()))),
+            (None, disp) => disp.unwrap_or(0),
+        };
+
+        let rip_relative = label.is_some() && ctx.options.position_independent;
+        let (base, index) = match (regs.as_slice(), rip_relative) {
+            ([], false) => return Ok(Operand::ImmPtr(Immediate::word(disp))),
+            ([], true) => (Register::rip(), None),
+            ([(index, scale)], true) => (Register::rip(), Some((*index, scale.unwrap_or(1)))),
+            ([(base, None)], false) => (*base, None),
+            ([(base, None), (index, scale)], false) | ([(index, scale @ Some(_)), (base, None)], false) => (*base, Some((*index, scale.unwrap_or(1)))),
+            _ => return Err(unexpected(&Token::Group(GroupDelim::Brack, group.to_vec()))),
+        };
+        Ok(Operand::Indexed(Address::new(base, index, disp)))
+    }
+
+    fn resize(operand : Operand, width : Width) -> Result<Operand> {
+        match operand {
+            Operand::Imm(value) => Ok(Operand::Imm(value.resize(width)?)),
+            _ => Ok(operand),
         }
     }
 
-    to_instructions!(
-        fn mov(left : Token, right : Token, ctx) FIRST 
-            { Self::movi2x(left, right, ctx) }
-            { Self::movip2x(left, right, ctx) }
-            { Self::movr2x(&left, right, ctx) }
-            { Self::movrp2x(&left, right, ctx) }
-    );
-    
-    to_instructions!(
-        fn movi2x(left : u16, right : Token, ctx) SECOND
-        { Err(Error::UnexpectedToken("movi2x".to_string(), format!("{right:?}"))) }
-        { Ok(vec![Instruction::movi2ip(
-            Immediate::new(Width::smallest_that_fits(*left), *left)?,
-            Immediate::new(Width::Word, *right)?,
-        )?]) }
-        { Ok(vec![Instruction::movi2r(Immediate::new(right.width(), *left)?, right)?]) }
-        { Ok(vec![Instruction::movi2rp(Immediate::new(Width::smallest_that_fits(*left), *left)?, right)?]) }
-    );
- 
-    to_instructions!(
-        fn movip2x(left : u16, right : Token, ctx) SECOND
-        { Err(Error::UnexpectedToken("movip2x".to_string(), format!("{right:?}"))) }
-        { Ok(vec![Instruction::movip2ip(
-            Immediate::new(Width::Word, *left)?,
-            Immediate::new(Width::Word, *right)?,
-        )?]) }
-        { Ok(vec![Instruction::movip2r(Immediate::new(Width::Word, *left)?, right)?]) }
-        { Ok(vec![Instruction::movip2rp(Immediate::new(Width::Word, *left)?, right)?]) }
-    );
-
-    to_instructions!(
-        fn movr2x(left : Register, right : Token, ctx) SECOND
-        { Err(Error::UnexpectedToken("movr2x".to_string(), format!("{right:?}"))) }
-        { Ok(vec![Instruction::movr2ip(*left, Immediate::new(Width::Word, *right)?)?]) }
-        { Ok(vec![Instruction::movr2r(*left, right)?]) }
-        { Ok(vec![Instruction::movr2rp(*left, right)?]) }
-    );
-
-    to_instructions!(
-        fn movrp2x(left : Register, right : Token, ctx) SECOND
-        { Err(Error::UnexpectedToken("movrp2x".to_string(), format!("{right:?}"))) }
-        { Ok(vec![Instruction::movrp2ip(*left, Immediate::new(Width::Word, *right)?)?]) }
-        { Ok(vec![Instruction::movrp2r(*left, right)?]) }
-        { Ok(vec![Instruction::movrp2rp(*left, right)?]) }
-    );
+    /// The width tag must agree with the width of the data, which it only sets by itself when nothing else does
+    fn check_width(instr : Instruction, width : Option<Width>) -> Result<Vec<Instruction>> {
+        match width {
+            Some(width) if instr.data_width() != Some(width) => Err(Error::InvalidOperands(instr)),
+            _ => Ok(vec![instr]),
+        }
+    }
+
+    /// Immediate sources take the width of the tag or a register destination,
+    /// memory to memory operations need the tag.
+    /// Both operands of xchg are exchanged, so memory can be on either side
+    fn binary(op : BinaryOp, width : Option<Width>, src : &Token, dest : &Token, ctx : &mut CompileContext, mnemonic : &Mnemonic) -> Result<Vec<Instruction>> {
+        let mut src = Self::operand(src, ParamIdx::FirstImm, ctx, mnemonic)?;
+        let mut dest = Self::operand(dest, ParamIdx::SecondImm, ctx, mnemonic)?;
+        if let Some(width) = width.or(dest.width()) {
+            src = Self::resize(src, width)?;
+        }
+        if op == BinaryOp::Xchg && src.is_memory() && !dest.is_memory() {
+            std::mem::swap(&mut src, &mut dest);
+        }
+        let instr = match (src.is_memory() && dest.is_memory(), width) {
+            (true, Some(width)) => Instruction::binary_mem(op, width, src, dest)?,
+            (true, None) => return Err(Error::AmbiguousWidth(mnemonic.to_string())),
+            (false, _) => Instruction::binary(op, src, dest)?,
+        };
+        Self::check_width(instr, width)
+    }
+
+    /// Operating on memory needs the tag
+    fn unary(op : UnaryOp, width : Option<Width>, dest : &Token, ctx : &mut CompileContext, mnemonic : &Mnemonic) -> Result<Vec<Instruction>> {
+        let dest = Self::operand(dest, ParamIdx::FirstImm, ctx, mnemonic)?;
+        let instr = match (dest.is_memory(), width) {
+            (true, Some(width)) => Instruction::unary_mem(op, width, dest)?,
+            (true, None) => return Err(Error::AmbiguousWidth(mnemonic.to_string())),
+            (false, _) => Instruction::unary(op, dest)?,
+        };
+        Self::check_width(instr, width)
+    }
+
+    /// Immediates are pushed as words unless tagged with `.b`, the tag only applies to immediates
+    fn push(width : Option<Width>, src : &Token, ctx : &mut CompileContext, mnemonic : &Mnemonic) -> Result<Vec<Instruction>> {
+        let src = match (Self::operand(src, ParamIdx::FirstImm, ctx, mnemonic)?, width) {
+            (src @ Operand::Imm(_), width) => Self::resize(src, width.unwrap_or(Width::Word))?,
+            (_, Some(width)) => return Err(Error::UnexpectedToken(mnemonic.to_string(), format!("{width:?}"))),
+            (src, None) => src,
+        };
+        Ok(vec![Instruction::push(src)?])
+    }
+
+    /// Immediate targets are addresses, so words
+    fn single(tok : &Token, ctx : &mut CompileContext, mnemonic : &Mnemonic) -> Result<Vec<Instruction>> {
+        let operand = Self::operand(tok, ParamIdx::FirstImm, ctx, mnemonic)?;
+        let instr = match mnemonic {
+            Mnemonic::Jmp(cond) => Instruction::jmp(*cond, Self::resize(operand, Width::Word)?),
+            Mnemonic::Call => Instruction::call(Self::resize(operand, Width::Word)?),
+            Mnemonic::Pop => Instruction::pop(operand),
+            _ => unreachable!("{mnemonic} takes a single operand"),
+        };
+        Ok(vec![instr?])
+    }
+
+    fn register(tok : &Token, mnemonic : &Mnemonic) -> Result<Register> {
+        match tok {
+            Token::Ident(ident) => Register::from(ident),
+            _ => None,
+        }.ok_or_else(|| Error::UnexpectedToken(mnemonic.to_string(), format!("{tok:?}")))
+    }
+
+    /// Ports are bytes, whether immediate or in a register
+    fn port(tok : &Token, param_idx : ParamIdx, ctx : &mut CompileContext, mnemonic : &Mnemonic) -> Result<Operand> {
+        let port = Self::operand(tok, param_idx, ctx, mnemonic)?;
+        Self::resize(port, Width::Byte)
+    }
+
+    fn int(vector : &Token) -> Result<Vec<Instruction>> {
+        let Token::Number(vector) = vector else {
+            return Err(Error::UnexpectedToken("int".to_string(), format!("{vector:?}")));
+        };
+        Ok(vec![Instruction::int(Immediate::signed(Width::Byte, *vector)?)?])
+    }
+
+    /// All the operands are registers
+    fn block(src : &Token, dest : &Token, count : &Token, op : &BlockOp, width : &Width) -> Result<Vec<Instruction>> {
+        let [src, dest, count] = [src, dest, count].map(|tok| match tok {
+            Token::Ident(ident) => Register::from(ident).ok_or_else(|| Error::UnexpectedToken("block".to_string(), format!("{tok:?}"))),
+            _ => Err(Error::UnexpectedToken("block".to_string(), format!("{tok:?}"))),
+        });
+        Ok(vec![Instruction::block(*op, *width, src?, dest?, count?)?])
+    }
 }
@@ -1,6 +1,6 @@
 #[allow(unused_imports)]
 use common::prelude::*;
-use sasm_lib::compile;
+use sasm_lib::{compile_with, Options};
 
 use clap::Parser;
 
@@ -14,13 +14,17 @@ pub struct Args {
     /// Output file
     #[arg(short = 'o', default_value = "main.bin")]
     out_path : String,
+
+    /// Make label references relative, so the code runs wherever it is loaded
+    #[arg(long)]
+    pic : bool,
 }
 
 fn main() -> Result<()> {
     let args = Args::parse();
 
     let code = read_file(&args.in_file)?;
-    let bytes = compile(&code)?;
+    let bytes = compile_with(&code, Options { position_independent : args.pic })?;
     write_file(&args.out_path, &bytes)
 }
 
@@ -34,6 +38,7 @@ fn write_file(fpath : &str, bytes : &[u8]) -> Result<()> {
 
     let mut fout = std::fs::OpenOptions::new()
         .create(true)
+        .truncate(true)
         .write(true)
         .open(fpath)
         .map_err(|err| Error::Misc(err.to_string()))?;
@@ -3,22 +3,41 @@ use common::prelude::*;
 use crate::Expr;
 use parser::{tokenize, Token, Scanner};
 
-fn parse_two_params(cb : impl FnOnce(Token, Token) -> Expr, toks : &mut Scanner<Token>, ctx : String) -> Result<Expr> {
-    let Some(t1) = toks.pop() else { return Err(Error::MissingToken(ctx)) };
-    let Some(comma) = toks.pop() else { return Err(Error::MissingToken(ctx)) };
-    if comma != Token::Punct(',') { return Err(Error::UnexpectedToken(ctx, format!("{comma:?}"))); }
-    let Some(t2) = toks.pop() else { return Err(Error::MissingToken(ctx)) };
+fn parse_params(count : usize, toks : &mut Scanner<Token>, ctx : String) -> Result<Vec<Token>> {
+    let mut params = Vec::with_capacity(count);
+    for idx in 0..count {
+        if idx > 0 {
+            let Some(comma) = toks.pop() else { return Err(Error::MissingToken(ctx)) };
+            if comma != Token::Punct(',') { return Err(Error::UnexpectedToken(ctx, format!("{comma:?}"))); }
+        }
+        let Some(t) = toks.pop() else { return Err(Error::MissingToken(ctx)) };
+        params.push(t);
+    }
+
+    Ok(params)
+}
 
-    Ok(cb(t1, t2))
+/// `.b` or `.w` right after the mnemonic
+fn parse_width(toks : &mut Scanner<Token>, ctx : &str) -> Result<Option<Width>> {
+    if toks.take(|t| *t == Token::Punct('.')).is_none() {
+        return Ok(None);
+    }
+    match toks.pop() {
+        Some(Token::Ident(tag)) if tag == "b" => Ok(Some(Width::Byte)),
+        Some(Token::Ident(tag)) if tag == "w" => Ok(Some(Width::Word)),
+        Some(t) => Err(Error::UnexpectedToken(ctx.to_string(), format!("{t:?}"))),
+        None => Err(Error::MissingToken(ctx.to_string())),
+    }
 }
 
 fn parse_instruction(ident : String, toks : &mut Scanner<Token>) -> Result<Expr> {
-    match &*ident {
-        "nop" => Ok(Expr::Nop),
-        "mov" => parse_two_params(Expr::Mov, toks, ident),
+    let Some(mnemonic) = Mnemonic::from(&ident) else {
+        return Err(Error::UnknownInstruction(ident));
+    };
+    let width = parse_width(toks, &ident)?;
+    let params = parse_params(mnemonic.arity(), toks, ident)?;
 
-        _ => Err(Error::UnknownInstruction(ident)),
-    }
+    Ok(Expr::Instruction(mnemonic, width, params))
 }
 
 fn parse_toks(t : Token, toks : &mut Scanner<Token>) -> Result<Expr> {
@@ -37,7 +56,7 @@ fn parse_toks(t : Token, toks : &mut Scanner<Token>) -> Result<Expr> {
 }
 
 pub fn parse(code : &str) -> Result<Vec<Expr>> {
-    let toks = tokenize(code);
+    let toks = tokenize(code)?;
     let mut toks = Scanner::new(toks);
 
     let mut exprs = Vec::new();
@@ -51,6 +70,7 @@ pub fn parse(code : &str) -> Result<Vec<Expr>> {
 #[cfg(test)]
 mod test {
     use super::*;
+    use parser::GroupDelim;
 
     #[test]
     fn label() {
@@ -58,7 +78,7 @@ mod test {
         let exprs = parse(code);
         assert_eq!(exprs, Ok(vec![
             Expr::Label("a_label".to_string()),
-            Expr::Nop,
+            Expr::Instruction(Mnemonic::Nop, None, vec![]),
         ]));
     }
 
@@ -67,7 +87,7 @@ mod test {
         let code = "nop";
         let exprs = parse(code);
         assert_eq!(exprs, Ok(vec![
-            Expr::Nop,
+            Expr::Instruction(Mnemonic::Nop, None, vec![]),
         ]));
     }
 
@@ -76,7 +96,65 @@ mod test {
         let code = "mov 0x600D, r0";
         let exprs = parse(code);
         assert_eq!(exprs, Ok(vec![
-            Expr::Mov(Token::Number(0x600D), Token::Ident("r0".to_string())),
+            Expr::Instruction(Mnemonic::Mov, None, vec![Token::Number(0x600D), Token::Ident("r0".to_string())]),
+        ]));
+    }
+
+    #[test]
+    fn alu() {
+        let code = "add rb0, rb1\nsbb 0x600D, [r0]";
+        let exprs = parse(code);
+        assert_eq!(exprs, Ok(vec![
+            Expr::Instruction(Mnemonic::Alu(AluOp::Add), None, vec![Token::Ident("rb0".to_string()), Token::Ident("rb1".to_string())]),
+            Expr::Instruction(Mnemonic::Alu(AluOp::Sbb), None, vec![Token::Number(0x600D), Token::Group(GroupDelim::Brack, vec![Token::Ident("r0".to_string())])]),
+        ]));
+    }
+
+    #[test]
+    fn width() {
+        let code = "mov.w [r0], [0xF337]\nnot.b [r1]";
+        let exprs = parse(code);
+        assert_eq!(exprs, Ok(vec![
+            Expr::Instruction(Mnemonic::Mov, Some(Width::Word), vec![Token::Group(GroupDelim::Brack, vec![Token::Ident("r0".to_string())]), Token::Group(GroupDelim::Brack, vec![Token::Number(0xF337)])]),
+            Expr::Instruction(Mnemonic::Unary(UnaryOp::Not), Some(Width::Byte), vec![Token::Group(GroupDelim::Brack, vec![Token::Ident("r1".to_string())])]),
+        ]));
+
+        let code = "mov.d [r0], [r1]";
+        let exprs = parse(code);
+        assert_eq!(exprs, Err(Error::UnexpectedToken("mov".to_string(), format!("{:?}", Token::Ident("d".to_string())))));
+    }
+
+    #[test]
+    fn unary() {
+        let code = "inc r0\ndec [0xF337]";
+        let exprs = parse(code);
+        assert_eq!(exprs, Ok(vec![
+            Expr::Instruction(Mnemonic::Unary(UnaryOp::Inc), None, vec![Token::Ident("r0".to_string())]),
+            Expr::Instruction(Mnemonic::Unary(UnaryOp::Dec), None, vec![Token::Group(GroupDelim::Brack, vec![Token::Number(0xF337)])]),
+        ]));
+    }
+
+    #[test]
+    fn block() {
+        let code = "movsb r0, r1, r2\nscasw r3 r1, r2";
+        let exprs = parse(code);
+        assert_eq!(exprs, Err(Error::UnexpectedToken("scasw".to_string(), format!("{:?}", Token::Ident("r1".to_string())))));
+
+        let code = "stosw r3, r1, r2";
+        let exprs = parse(code);
+        assert_eq!(exprs, Ok(vec![
+            Expr::Instruction(Mnemonic::Block(BlockOp::Fill, Width::Word), None, vec![Token::Ident("r3".to_string()), Token::Ident("r1".to_string()), Token::Ident("r2".to_string())]),
+        ]));
+    }
+
+    #[test]
+    fn jmp() {
+        let code = "jmp r5\nloop: jz loop";
+        let exprs = parse(code);
+        assert_eq!(exprs, Ok(vec![
+            Expr::Instruction(Mnemonic::Jmp(Condition::Always), None, vec![Token::Ident("r5".to_string())]),
+            Expr::Label("loop".to_string()),
+            Expr::Instruction(Mnemonic::Jmp(Condition::Zero), None, vec![Token::Ident("loop".to_string())]),
         ]));
     }
 }